@@ -1,20 +1,49 @@
-use std::net::IpAddr;
-use std::net::Ipv4Addr;
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::Duration;
+use std::time::Instant;
 
 use log::debug;
 use log::trace;
+use rand::Rng;
 use tokio::net::UdpSocket;
-use tokio::time::timeout;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::Receiver;
+use tokio::time::interval;
+use tokio::task::JoinSet;
+use tokio::time::sleep_until;
+use tokio::time::timeout_at;
 
+use crate::http_helper::default_product_token;
 use crate::http_helper::generate_ssdp_discover;
+use crate::http_helper::generate_ssdp_unicast_discover;
+use crate::http_helper::locations;
+use crate::http_helper::parse_ssdp_discover_answer;
+use crate::http_helper::DEFAULT_EXPIRATION;
+use crate::interface::resolve_interfaces;
+use crate::interface::Interface;
+use crate::interface::NetworkInterface;
+use crate::message::SsdpMessage;
 use crate::service::ServiceDescription;
 use crate::socket_helper::join_socket;
+use crate::socket_helper::set_multicast_if;
+use crate::socket_helper::SocketBuilder;
+use crate::socket_helper::target_addr;
 use crate::MulticastAddr;
-use crate::SSDP_PORT;
+use crate::Error;
+use crate::Protocol;
+
+/// How long a search waits at most, for timeouts too large to be added to the current instant like [Duration::MAX]
+///
+/// About 30 years, as good as waiting forever.
+const FAR_FUTURE: Duration = Duration::from_secs(86400 * 365 * 30);
+
+/// How long [Client::search_unicast] keeps listening after the first answer
+///
+/// The other registrations of the Service answer right after it, there is no `MX` to wait for.
+const UNICAST_GRACE: Duration = Duration::from_millis(100);
 
 /// The SSDP Client
 pub struct Client {
@@ -24,10 +53,28 @@ pub struct Client {
     /// |------------------|------------------|------------|-------------------|
     /// | upnp:uuid:k91... | upnp:clockradio  | 3 days     | http://foo.com/cr |
     /// | uuid:x7z...      | ms:wince         | 1 week     | http://msce/win   |
-    services: Arc<Mutex<Vec<ServiceDescription>>>,
+    services: Arc<Mutex<Vec<DiscoveredService>>>,
 
     /// Timeout - used to wait for incoming answers
     timeout: Duration,
+
+    /// `MX` - the maximum number of seconds Services may delay their answers
+    mx: u32,
+
+    /// How often each `M-SEARCH` is sent, to survive the loss of single UDP packets
+    search_count: usize,
+
+    /// The flavour of SSDP used for searching
+    protocol: Protocol,
+
+    /// `USER-AGENT` sent in [Protocol::Upnp] mode
+    user_agent: String,
+
+    /// The interfaces to search and watch on, empty lets the OS choose
+    interfaces: Vec<Interface>,
+
+    /// Options of the sockets
+    socket_builder: SocketBuilder,
 }
 
 impl Default for Client {
@@ -35,14 +82,625 @@ impl Default for Client {
         Self {
             services: Arc::new(Mutex::new(vec![])),
             timeout: Duration::from_secs(5),
+            mx: 1,
+            search_count: 2,
+            protocol: Protocol::default(),
+            user_agent: default_product_token(),
+            interfaces: vec![],
+            socket_builder: SocketBuilder::default(),
         }
     }
 }
 
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// A change of the Services within the network, received by [Client::watch]
+pub enum ClientEvent {
+    /// A Service announced itself with `ssdp:alive` for the first time
+    ServiceAppeared(ServiceDescription),
+
+    /// A known Service announced itself with a changed description or sent `ssdp:update`
+    ServiceUpdated(ServiceDescription),
+
+    /// A known Service did not re-announce itself within its expiration
+    ServiceExpired(ServiceDescription),
+
+    /// A known Service left the network with `ssdp:byebye`
+    ServiceByebye(ServiceDescription),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+/// What a single search of [Client::discover] brought in
+pub struct DiscoveryStats {
+    /// How often the `M-SEARCH` was sent, see [Client::set_search_count]
+    pub searches_sent: usize,
+
+    /// All answers received, a Service answering every retransmitted `M-SEARCH` is counted each time
+    pub responses: usize,
+
+    /// The answers to each `M-SEARCH` sent, summing up to `responses`
+    ///
+    /// Every `M-SEARCH` is sent from sockets of its own, so answers are counted for the one they answer,
+    /// even if they arrive after the next one was sent.
+    pub responses_per_attempt: Vec<usize>,
+
+    /// The number of distinct Services found, answers of the same USN counted once
+    pub services_found: usize,
+
+    /// The number of distinct Services found per search target, see [Client::discover_targets]
+    pub services_per_target: HashMap<String, usize>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// An answer to a `M-SEARCH`, received by [Client::discover_stream]
+pub struct DiscoveryResponse {
+    /// The Service that answered
+    pub description: ServiceDescription,
+
+    /// The address the answer came from
+    pub source: SocketAddr,
+
+    /// When the answer arrived
+    pub received_at: Instant,
+
+    /// The search targets the Service answered, see [Client::discover_targets]
+    ///
+    /// Holds `ssdp:all` as well as the type of the Service if both were searched
+    pub search_targets: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// A Service found by the [Client]
+pub struct DiscoveredService {
+    /// The description sent by the Service
+    ///
+    /// [ServiceDescription::location] holds the preferred one of [DiscoveredService::locations]
+    pub description: ServiceDescription,
+
+    /// All URLs the Service can be reached at, the preferred one first
+    ///
+    /// Taken from the UPnP `LOCATION` header and the URLs within the `AL` header
+    pub locations: Vec<String>,
+
+    /// The UPnP `SERVER` header, naming the OS and product of the Service
+    pub server: Option<String>,
+
+    /// The UPnP `BOOTID.UPNP.ORG` header, increased every time the device comes up
+    pub boot_id: Option<u32>,
+
+    /// The UPnP `CONFIGID.UPNP.ORG` header, changed whenever the device description changes
+    pub config_id: Option<u32>,
+
+    /// The UPnP `SEARCHPORT.UPNP.ORG` header, the port to send unicast `M-SEARCH` to if not the SSDP port
+    pub search_port: Option<u16>,
+
+    /// Every address the Service was seen at
+    ///
+    /// A Service reachable in several scopes, see [Client::discover_scopes], is seen at several addresses
+    pub addresses: Vec<SocketAddr>,
+
+    /// The name of the network interface the Service was seen on
+    ///
+    /// Only known if the [Client] was given interfaces, see [Client::set_interfaces]
+    pub interface: Option<String>,
+
+    /// The moment this entry expires, unless the Service announces itself again
+    ///
+    /// Derived from the `max-age` of the `Cache-Control` header when the Service was last seen
+    pub expires_at: Instant,
+}
+
+impl DiscoveredService {
+    /// Creates an entry from a `M-SEARCH` answer or a `ssdp:alive`
+    ///
+    /// Returns `None` for any other message
+    pub(crate) fn from_message(message: SsdpMessage) -> Option<Self> {
+        let max_age = message.max_age();
+        let (usn, service_type_uri, al, location, server, boot_id, config_id, search_port) =
+            match message {
+                SsdpMessage::SearchResponse {
+                    st,
+                    usn,
+                    al,
+                    location,
+                    server,
+                    boot_id,
+                    config_id,
+                    search_port,
+                    ..
+                } => (usn, st, al, location, server, boot_id, config_id, search_port),
+                SsdpMessage::NotifyAlive {
+                    nt,
+                    usn,
+                    al,
+                    location,
+                    server,
+                    boot_id,
+                    config_id,
+                    search_port,
+                    ..
+                } => (usn, nt, al, location, server, boot_id, config_id, search_port),
+                _ => return None,
+            };
+
+        let locations = locations(&al, location.as_deref());
+        let expiration = max_age.unwrap_or(DEFAULT_EXPIRATION);
+
+        Some(DiscoveredService {
+            description: ServiceDescription {
+                usn_uri: usn,
+                service_type_uri,
+                expiration,
+                location: locations.first().cloned().unwrap_or_default(),
+            },
+            locations,
+            server,
+            boot_id,
+            config_id,
+            search_port,
+            addresses: vec![],
+            interface: None,
+            expires_at: Instant::now() + Duration::from_secs(expiration.into()),
+        })
+    }
+
+    /// Returns `true` once the Service did not announce itself within its expiration
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Instant::now()
+    }
+}
+
+/// Adds a Service to the list or replaces the one with the same USN
+///
+/// The addresses the replaced entry was seen at are kept, unless it expired.
+/// Returns the replaced entry
+fn store_service(
+    services: &Mutex<Vec<DiscoveredService>>,
+    mut new_service: DiscoveredService,
+) -> Option<DiscoveredService> {
+    let mut services_guard = services.lock().unwrap();
+
+    for service in services_guard.iter_mut() {
+        if service.description.usn_uri == new_service.description.usn_uri {
+            if !service.is_expired() {
+                for address in &service.addresses {
+                    if !new_service.addresses.contains(address) {
+                        new_service.addresses.push(*address);
+                    }
+                }
+            }
+
+            return Some(std::mem::replace(service, new_service));
+        }
+    }
+
+    services_guard.push(new_service);
+
+    None
+}
+
+/// Removes the Service with the given USN from the list
+fn remove_service(
+    services: &Mutex<Vec<DiscoveredService>>,
+    usn: &str,
+) -> Option<ServiceDescription> {
+    let mut services_guard = services.lock().unwrap();
+    let index = services_guard
+        .iter()
+        .position(|service| service.description.usn_uri == usn)?;
+
+    Some(services_guard.remove(index).description)
+}
+
+/// Removes all expired Services from the list
+fn evict_expired(services: &Mutex<Vec<DiscoveredService>>) -> Vec<ServiceDescription> {
+    let mut services_guard = services.lock().unwrap();
+    let (expired, live) = std::mem::take(&mut *services_guard)
+        .into_iter()
+        .partition(DiscoveredService::is_expired);
+    *services_guard = live;
+
+    expired
+        .into_iter()
+        .map(|service: DiscoveredService| service.description)
+        .collect()
+}
+
+/// Applies a received `NOTIFY` to the list of Services
+///
+/// Returns the resulting [ClientEvent], if there is one
+fn handle_notify(
+    services: &Mutex<Vec<DiscoveredService>>,
+    message: SsdpMessage,
+    source: SocketAddr,
+    interface: Option<String>,
+) -> Option<ClientEvent> {
+    match message {
+        message @ SsdpMessage::NotifyAlive { .. } => {
+            let mut new_service = DiscoveredService::from_message(message)?;
+            new_service.addresses = vec![source];
+            new_service.interface = interface;
+            let description = new_service.description.clone();
+
+            match store_service(services, new_service.clone()) {
+                Some(old_service) if old_service.is_expired() => {
+                    Some(ClientEvent::ServiceAppeared(description))
+                }
+                Some(old_service)
+                    if old_service.description != new_service.description
+                        || old_service.boot_id != new_service.boot_id
+                        || old_service.config_id != new_service.config_id =>
+                {
+                    Some(ClientEvent::ServiceUpdated(description))
+                }
+                Some(_) => None,
+                None => Some(ClientEvent::ServiceAppeared(description)),
+            }
+        }
+        SsdpMessage::NotifyUpdate {
+            nt,
+            usn,
+            al,
+            location,
+            config_id,
+            next_boot_id,
+            search_port,
+            ..
+        } => {
+            let mut services_guard = services.lock().unwrap();
+            let service = services_guard
+                .iter_mut()
+                .find(|service| service.description.usn_uri == usn && !service.is_expired())?;
+
+            service.locations = locations(&al, location.as_deref());
+            service.boot_id = next_boot_id.or(service.boot_id);
+            service.config_id = config_id.or(service.config_id);
+            service.search_port = search_port.or(service.search_port);
+            service.description.service_type_uri = nt;
+            service.description.location = service.locations.first().cloned().unwrap_or_default();
+
+            Some(ClientEvent::ServiceUpdated(service.description.clone()))
+        }
+        SsdpMessage::NotifyByebye { usn, .. } => {
+            remove_service(services, &usn).map(ClientEvent::ServiceByebye)
+        }
+        _ => None,
+    }
+}
+
+/// A socket a `M-SEARCH` is sent and answered on
+struct SearchSocket {
+    socket: Arc<UdpSocket>,
+
+    /// The address to send the `M-SEARCH` to, the multicast group unless searching a single Service
+    multicast_addr: SocketAddr,
+
+    /// The name of the interface, if chosen
+    interface: Option<String>,
+
+    /// Which `M-SEARCH` of the schedule is sent from this socket, counting from 0
+    attempt: usize,
+
+    /// The `M-SEARCH` to send, one per search target
+    messages: Vec<String>,
+}
+
+/// Creates a UDP socket per interface to search `address` on, so we know where answers came in
+///
+/// - `attempt` - Which `M-SEARCH` of the schedule the sockets send, so we know which one answers came for
+fn search_sockets(
+    socket_builder: &SocketBuilder,
+    address: &MulticastAddr,
+    interfaces: &[NetworkInterface],
+    messages: &[String],
+    attempt: usize,
+) -> Result<Vec<SearchSocket>, Error> {
+    let mut sockets = vec![];
+
+    if interfaces.is_empty() {
+        let socket = Arc::new(socket_builder.bind_any(address)?);
+        join_socket(address, socket.clone(), None)?;
+        sockets.push(SearchSocket {
+            socket,
+            multicast_addr: target_addr(address, None),
+            interface: None,
+            attempt,
+            messages: messages.to_vec(),
+        });
+    }
+    for interface in interfaces {
+        let socket = Arc::new(socket_builder.bind_any(address)?);
+        join_socket(address, socket.clone(), Some(interface))?;
+        set_multicast_if(address, &socket, interface)?;
+        sockets.push(SearchSocket {
+            socket,
+            multicast_addr: target_addr(address, Some(interface)),
+            interface: Some(interface.name.clone()),
+            attempt,
+            messages: messages.to_vec(),
+        });
+    }
+
+    Ok(sockets)
+}
+
+/// Stores all answers to a `M-SEARCH` received on `socket` until `deadline`
+///
+/// - `interface` - The name of the interface `socket` sends and receives on, if chosen
+/// - `attempt` - Which `M-SEARCH` was sent from `socket`, passed on with every answer
+/// - `searches` - The search targets searched for, answers matching none of them are dropped
+/// - `answers` - Gets every answer stored, as well as messages that could not be received or parsed
+async fn receive_answers(
+    socket: Arc<UdpSocket>,
+    interface: Option<String>,
+    attempt: usize,
+    searches: Vec<String>,
+    deadline: tokio::time::Instant,
+    services: Arc<Mutex<Vec<DiscoveredService>>>,
+    answers: mpsc::Sender<(usize, Result<DiscoveryResponse, Error>)>,
+) {
+    // Create a buffer to store the received data
+    let mut buf = vec![0; 1024];
+
+    loop {
+        match timeout_at(deadline, socket.recv_from(&mut buf)).await {
+            Ok(Ok((len, addr))) => {
+                trace!(
+                    "Received {} bytes from {}: {:#?}",
+                    len,
+                    addr,
+                    String::from_utf8_lossy(&buf[..len])
+                );
+
+                let message = match parse_ssdp_discover_answer(&buf[..len]) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        if answers.send((attempt, Err(Error::Parse(e)))).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+                let Some(mut new_service) = message.and_then(DiscoveredService::from_message) else {
+                    continue;
+                };
+                let search_targets = search_targets(&searches, &new_service.description);
+                if search_targets.is_empty() {
+                    trace!("{} answered none of {:?}", new_service.description.usn_uri, searches);
+                    continue;
+                }
+                new_service.interface = interface.clone();
+                new_service.addresses = vec![addr];
+
+                let response = DiscoveryResponse {
+                    description: new_service.description.clone(),
+                    source: addr,
+                    received_at: Instant::now(),
+                    search_targets: search_targets.into_iter().cloned().collect(),
+                };
+
+                // Answers to a retransmitted M-SEARCH replace the ones before
+                store_service(&services, new_service);
+
+                if answers.send((attempt, Ok(response))).await.is_err() {
+                    break; // The search is over
+                }
+            }
+            Ok(Err(e)) => {
+                trace!("Error receiving response: {}", e);
+                if answers.send((attempt, Err(Error::Receive(e)))).await.is_err() {
+                    break;
+                }
+            }
+            Err(_) => {
+                break; // Timeout reached
+            }
+        }
+    }
+}
+
+/// Picks when to send each of `count` `M-SEARCH`, relative to the start of the search
+///
+/// The first one is sent right away, the others are spread evenly over the `MX` seconds,
+/// each delayed by a random jitter of up to half its share, so Clients searching at once don't keep colliding.
+pub(crate) fn search_schedule(count: usize, mx: u32) -> Vec<Duration> {
+    let share = Duration::from_secs(mx.into()) / count.max(1) as u32;
+
+    (0..count.max(1))
+        .map(|attempt| {
+            if attempt == 0 || (share / 2).is_zero() {
+                return share * attempt as u32;
+            }
+
+            share * attempt as u32 + rand::thread_rng().gen_range(Duration::ZERO..share / 2)
+        })
+        .collect()
+}
+
+/// The search targets out of `searches` the Service of `description` answered
+///
+/// Services answer with the search target as `ST`, except for `ssdp:all`, which they answer with their own type.
+/// So an answer matches both its own type and `ssdp:all`, if both were searched.
+pub(crate) fn search_targets<'a>(searches: &'a [String], description: &ServiceDescription) -> Vec<&'a String> {
+    searches
+        .iter()
+        .filter(|search| **search == description.service_type_uri || *search == "ssdp:all")
+        .collect()
+}
+
+/// A search in progress, listening for answers on all its sockets
+struct Search {
+    sockets: Vec<SearchSocket>,
+
+    /// When the remaining `M-SEARCH` are due, relative to `start`
+    schedule: std::vec::IntoIter<Duration>,
+
+    start: tokio::time::Instant,
+
+    /// The answers matching the searched targets from all sockets, with the attempt they answer
+    answers: Receiver<(usize, Result<DiscoveryResponse, Error>)>,
+
+    receivers: JoinSet<()>,
+
+    services: Arc<Mutex<Vec<DiscoveredService>>>,
+
+    stats: DiscoveryStats,
+
+    /// The last message that could not be received or parsed, see [Client::search_unicast]
+    failure: Option<Error>,
+}
+
+impl Search {
+    /// Starts listening for answers on all `sockets` until `listen_timeout` has passed and sends the first `M-SEARCH`
+    ///
+    /// - `searches` - The search targets searched for, answers are attributed to the ones they match
+    /// - `schedule` - When to send each `M-SEARCH`, see [search_schedule], the first one is sent right away
+    async fn start(
+        sockets: Vec<SearchSocket>,
+        searches: Vec<String>,
+        schedule: Vec<Duration>,
+        listen_timeout: Duration,
+        services: Arc<Mutex<Vec<DiscoveredService>>>,
+    ) -> Result<Self, Error> {
+        // Listen for service replies until timeout
+        let start = tokio::time::Instant::now();
+        let deadline = start.checked_add(listen_timeout).unwrap_or(start + FAR_FUTURE);
+        let (answers_tx, answers) = mpsc::channel(16);
+        let mut receivers = JoinSet::new();
+
+        for search_socket in &sockets {
+            receivers.spawn(receive_answers(
+                search_socket.socket.clone(),
+                search_socket.interface.clone(),
+                search_socket.attempt,
+                searches.clone(),
+                deadline,
+                services.clone(),
+                answers_tx.clone(),
+            ));
+        }
+
+        let mut search = Search {
+            sockets,
+            schedule: schedule.into_iter(),
+            start,
+            answers,
+            receivers,
+            services,
+            stats: DiscoveryStats::default(),
+            failure: None,
+        };
+        if search.schedule.next().is_some() {
+            search.send().await?;
+        }
+
+        Ok(search)
+    }
+
+    /// Multicasts the next `M-SEARCH` on all sockets of its attempt
+    async fn send(&mut self) -> Result<(), Error> {
+        let attempt = self.stats.searches_sent;
+        self.stats.searches_sent += 1;
+        self.stats.responses_per_attempt.push(0);
+
+        for search_socket in self.sockets.iter().filter(|search_socket| search_socket.attempt == attempt) {
+            for message in &search_socket.messages {
+                search_socket
+                    .socket
+                    .send_to(message.as_bytes(), &search_socket.multicast_addr)
+                    .await
+                    .map_err(Error::Send)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends the remaining `M-SEARCH` as scheduled and collects the answers until the listen timeout has passed
+    ///
+    /// - `done` - Stops the search early once it returns `true` for the Services found so far
+    /// - `grace` - How long to keep collecting answers once `done` returned `true`
+    /// - `responses` - Gets the first answer of every Service, the search stops once it is closed
+    async fn run<F>(
+        &mut self,
+        mut done: F,
+        grace: Duration,
+        responses: Option<mpsc::Sender<DiscoveryResponse>>,
+    ) -> Result<DiscoveryStats, Error>
+    where
+        F: FnMut(&[ServiceDescription]) -> bool,
+    {
+        let mut found: Vec<ServiceDescription> = vec![];
+        let mut next_search = self.schedule.next();
+        // `done` may be satisfied without any answer
+        let mut grace_deadline = done(&found).then(|| tokio::time::Instant::now() + grace);
+
+        loop {
+            tokio::select! {
+                _ = sleep_until(self.start + next_search.unwrap_or_default()), if next_search.is_some() => {
+                    self.send().await?;
+                    next_search = self.schedule.next();
+                }
+                _ = sleep_until(grace_deadline.unwrap_or(self.start)), if grace_deadline.is_some() => {
+                    break;
+                }
+                _ = async { responses.as_ref().unwrap().closed().await }, if responses.is_some() => {
+                    break; // Nobody is interested in the answers anymore
+                }
+                answer = self.answers.recv() => {
+                    // All receivers reached the timeout
+                    let Some((attempt, answer)) = answer else {
+                        break;
+                    };
+                    let answer = match answer {
+                        Ok(answer) => answer,
+                        Err(e) => {
+                            debug!("Bad answer: {}", e);
+                            self.failure = Some(e);
+                            continue;
+                        }
+                    };
+
+                    self.stats.responses += 1;
+                    if let Some(responses) = self.stats.responses_per_attempt.get_mut(attempt) {
+                        *responses += 1;
+                    }
+                    if found.iter().any(|service| service.usn_uri == answer.description.usn_uri) {
+                        continue;
+                    }
+
+                    found.push(answer.description.clone());
+                    for search_target in &answer.search_targets {
+                        *self.stats.services_per_target.entry(search_target.clone()).or_default() += 1;
+                    }
+                    if let Some(responses) = &responses {
+                        if responses.send(answer).await.is_err() {
+                            break;
+                        }
+                    }
+                    if done(&found) {
+                        if grace.is_zero() {
+                            break;
+                        }
+                        grace_deadline.get_or_insert_with(|| tokio::time::Instant::now() + grace);
+                    }
+                }
+            }
+        }
+
+        self.receivers.shutdown().await;
+
+        debug!("Services found: {:#?}", self.services);
+
+        self.stats.services_found = found.len();
+
+        Ok(self.stats.clone())
+    }
+}
+
 impl Client {
-    // TODO there could be a blocking channel which also receives the NOTIFY and BYEBYE calls
     /// Discover SSDP Services
-    /// - `identifier`: The unique Identifier for this Client e.g. `uuid:83760048-2d32-4e48-854f-f63a8fa9fd09`
+    /// - `identifier`: The unique Identifier for this Client e.g. `uuid:83760048-2d32-4e48-854f-f63a8fa9fd09`,
+    ///   not sent in [Protocol::Upnp] mode
     /// - `address`: In which scope do you want to scan?
     /// - `search`: `ssdp:all` to find all SSDP Services or custom Service Types to look for
     pub async fn discover(
@@ -50,130 +708,492 @@ impl Client {
         identifier: String,
         address: MulticastAddr,
         search: String,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // Create a UDP socket
-        let local_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
-        let socket = Arc::new(UdpSocket::bind(local_addr).await?);
-        let multicast_addr: SocketAddr = SocketAddr::new(address.get_ip(), SSDP_PORT);
+    ) -> Result<DiscoveryStats, Error> {
+        self.discover_scopes(identifier, &[address], search).await
+    }
 
-        join_socket(&address, socket.clone())?;
+    /// Discover SSDP Services in the IPv4 and both IPv6 scopes at once
+    ///
+    /// Same as [Client::discover_scopes] with [MulticastAddr::V4], [MulticastAddr::V6LinkLocal] and [MulticastAddr::V6SiteLocal]
+    pub async fn discover_dual_stack(
+        &self,
+        identifier: String,
+        search: String,
+    ) -> Result<DiscoveryStats, Error> {
+        self.discover_scopes(
+            identifier,
+            &[
+                MulticastAddr::V4,
+                MulticastAddr::V6LinkLocal,
+                MulticastAddr::V6SiteLocal,
+            ],
+            search,
+        )
+        .await
+    }
 
-        let discover_message = generate_ssdp_discover(identifier, search, &address);
+    /// Discover SSDP Services in several scopes at once
+    ///
+    /// Sends the `M-SEARCH` to every scope on every interface concurrently. Answers are merged by USN,
+    /// [DiscoveredService::addresses] holds every address a Service answered from.
+    ///
+    /// Scopes that can't be searched, like IPv6 on a host without IPv6, are skipped unless none is left.
+    /// Fails with [Error::EmptySearch] if `addresses` is empty.
+    pub async fn discover_scopes(
+        &self,
+        identifier: String,
+        addresses: &[MulticastAddr],
+        search: String,
+    ) -> Result<DiscoveryStats, Error> {
+        self.search(identifier, addresses, vec![search], |_| false).await
+    }
+
+    /// Discover SSDP Services of several search targets at once
+    ///
+    /// Same as [Client::discover], but sends one `M-SEARCH` per search target on the same socket and waits
+    /// for all answers together. [DiscoveryStats::services_per_target] tells how many Services answered each of them.
+    ///
+    /// Fails with [Error::EmptySearch] if `searches` is empty.
+    pub async fn discover_targets(
+        &self,
+        identifier: String,
+        address: MulticastAddr,
+        searches: Vec<String>,
+    ) -> Result<DiscoveryStats, Error> {
+        self.search(identifier, &[address], searches, |_| false).await
+    }
 
-        // Multicast search request
-        socket
-            .send_to(discover_message.as_bytes(), &multicast_addr)
+    /// Discover SSDP Services until `done` is satisfied
+    ///
+    /// Same as [Client::discover], but returns as soon as `done` returns `true` for the Services found so far,
+    /// in the order they first answered. Wait for a known set of Services with e.g.
+    ///
+    /// ```text
+    /// |found| expected.iter().all(|usn| found.iter().any(|service| &service.usn_uri == usn))
+    /// ```
+    ///
+    /// or for a number of them with `|found| found.len() >= 3`.
+    ///
+    /// Fails with [Error::Timeout] if `done` is still not satisfied when the timeout is reached,
+    /// the Services found are kept anyway.
+    pub async fn discover_until<F>(
+        &self,
+        identifier: String,
+        address: MulticastAddr,
+        search: String,
+        mut done: F,
+    ) -> Result<DiscoveryStats, Error>
+    where
+        F: FnMut(&[ServiceDescription]) -> bool,
+    {
+        let mut satisfied = false;
+        let stats = self
+            .search(identifier, &[address], vec![search], |found| {
+                satisfied = done(found);
+                satisfied
+            })
             .await?;
 
-        // Create a buffer to store the received data
-        let mut buf = vec![0; 1024];
+        match satisfied {
+            true => Ok(stats),
+            false => Err(Error::Timeout),
+        }
+    }
 
-        // Listen for service replies
-        // Define a timeout duration for listening for responses
-        let start = tokio::time::Instant::now();
+    /// Discover the first SSDP Service answering `search`
+    ///
+    /// Returns as soon as a Service answers, fails with [Error::Timeout] if none does in time.
+    pub async fn discover_first(
+        &self,
+        identifier: String,
+        address: MulticastAddr,
+        search: String,
+    ) -> Result<ServiceDescription, Error> {
+        let mut first = None;
+        self.discover_until(identifier, address, search, |found| {
+            first = found.first().cloned();
+            first.is_some()
+        })
+        .await?;
 
-        // Listen for service replies until timeout
-        while start.elapsed() < self.timeout {
-            match timeout(
-                self.timeout - start.elapsed(),
-                socket.recv_from(&mut buf),
+        first.ok_or(Error::Timeout)
+    }
+
+    /// Discover SSDP Services, getting each one as soon as it answers
+    ///
+    /// Same as [Client::discover], but the search runs in the background and every Service found is sent
+    /// to the returned receiver once, together with where its answer came from and when it arrived.
+    /// The receiver is closed when the timeout is reached, the search stops early once it is dropped.
+    ///
+    /// Fails if the first `M-SEARCH` can't be sent, a later one failing closes the receiver early.
+    pub async fn discover_stream(
+        &self,
+        identifier: String,
+        address: MulticastAddr,
+        search: String,
+    ) -> Result<Receiver<DiscoveryResponse>, Error> {
+        let searches = vec![search];
+        let schedule = search_schedule(self.search_count, self.mx);
+        let sockets = self.prepare_search(identifier, &[address], &searches, schedule.len())?;
+        let listen_timeout = self.listen_timeout(&schedule);
+        let mut search = Search::start(sockets, searches, schedule, listen_timeout, self.services.clone()).await?;
+        let (responses, receiver) = mpsc::channel(64);
+
+        tokio::spawn(async move {
+            if let Err(e) = search.run(|_| false, Duration::ZERO, Some(responses)).await {
+                debug!("Search failed: {}", e);
+            }
+        });
+
+        Ok(receiver)
+    }
+
+    /// Search a single Service at a known address, e.g. to check it is still alive
+    ///
+    /// Sends a unicast `M-SEARCH` to `address`, the SSDP port of the Service, without flooding the network.
+    /// Having no `MX`, the Service answers right away. Returns shortly after the first answer with all
+    /// registrations answering `search` by then, and fails with [Error::Timeout] if none did within the timeout.
+    /// If only malformed answers came back, it fails with the last [Error::Parse] instead,
+    /// or with [Error::Receive] if receiving failed.
+    ///
+    /// Several Services sharing the SSDP port of a host with `SO_REUSEPORT`, like [crate::service::Service] does,
+    /// only get the unicast `M-SEARCH` delivered to one of them by the OS, so only that one answers.
+    pub async fn search_unicast(
+        &self,
+        identifier: String,
+        address: SocketAddr,
+        search: String,
+    ) -> Result<Vec<ServiceDescription>, Error> {
+        let message = match self.protocol {
+            Protocol::Draft => generate_ssdp_unicast_discover(Some(identifier), search.clone(), address, None),
+            Protocol::Upnp => {
+                generate_ssdp_unicast_discover(None, search.clone(), address, Some(self.user_agent.clone()))
+            }
+        };
+        let socket = SearchSocket {
+            socket: Arc::new(self.socket_builder.bind_unicast(&address)?),
+            multicast_addr: address,
+            interface: None,
+            attempt: 0,
+            messages: vec![message],
+        };
+
+        let mut answered = vec![];
+        let mut search =
+            Search::start(vec![socket], vec![search], vec![Duration::ZERO], self.timeout, self.services.clone()).await?;
+        search
+            .run(
+                |found| {
+                    answered = found.to_vec();
+                    !answered.is_empty()
+                },
+                UNICAST_GRACE,
+                None,
             )
+            .await?;
+
+        match (answered.is_empty(), search.failure) {
+            (false, _) => Ok(answered),
+            (true, Some(e)) => Err(e),
+            (true, None) => Err(Error::Timeout),
+        }
+    }
+
+    /// Searches all `addresses` until the timeout is reached or `done` returns `true` for the Services found so far
+    async fn search<F>(
+        &self,
+        identifier: String,
+        addresses: &[MulticastAddr],
+        searches: Vec<String>,
+        done: F,
+    ) -> Result<DiscoveryStats, Error>
+    where
+        F: FnMut(&[ServiceDescription]) -> bool,
+    {
+        let schedule = search_schedule(self.search_count, self.mx);
+        let sockets = self.prepare_search(identifier, addresses, &searches, schedule.len())?;
+        let listen_timeout = self.listen_timeout(&schedule);
+
+        Search::start(sockets, searches, schedule, listen_timeout, self.services.clone())
+            .await?
+            .run(done, Duration::ZERO, None)
             .await
-            {
-                Ok(Ok((len, addr))) => {
-                    let response_message = String::from_utf8_lossy(&buf[..len]).to_string();
-                    trace!(
-                        "Received {} bytes from {}: {:#?}",
-                        len,
-                        addr,
-                        response_message
-                    );
-
-                    let mut headers = [httparse::EMPTY_HEADER; 64];
-                    let mut resp = httparse::Response::new(&mut headers);
-
-                    if resp.parse(&buf[..len]).is_err() {
-                        trace!("Could not parse request to HTTP");
-                        continue;
-                    }
+    }
 
-                    if resp.code.is_none() || resp.code.is_some_and(|code| 200 != code) {
-                        trace!("Response Code is not 200");
-                        continue;
-                    }
+    /// Opens the sockets to search all `addresses` on, a set of its own for each of the `attempts`
+    fn prepare_search(
+        &self,
+        identifier: String,
+        addresses: &[MulticastAddr],
+        searches: &[String],
+        attempts: usize,
+    ) -> Result<Vec<SearchSocket>, Error> {
+        if addresses.is_empty() || searches.is_empty() {
+            return Err(Error::EmptySearch);
+        }
 
-                    // TODO the response must be in exact order right now
-                    let usn = resp.headers.get(4);
-                    if usn.is_none() || usn.is_some_and(|usn| usn.name != "USN") {
-                        trace!("USN header is not present");
-                        continue;
-                    }
+        let interfaces = resolve_interfaces(&self.interfaces)?;
 
-                    let st = resp.headers.get(3);
-                    if st.is_none() || st.is_some_and(|st| st.name != "ST") {
-                        trace!("ST header is not present");
-                        continue;
-                    }
+        let mut sockets = vec![];
+        let mut last_error = None;
+        for address in addresses {
+            let discover_messages: Vec<String> = searches
+                .iter()
+                .map(|search| match self.protocol {
+                    Protocol::Draft => generate_ssdp_discover(
+                        Some(identifier.clone()),
+                        search.clone(),
+                        address,
+                        self.mx,
+                        None,
+                    ),
+                    Protocol::Upnp => generate_ssdp_discover(
+                        None,
+                        search.clone(),
+                        address,
+                        self.mx,
+                        Some(self.user_agent.clone()),
+                    ),
+                })
+                .collect();
 
-                    let al = resp.headers.get(5);
-                    if al.is_none() || al.is_some_and(|al| al.name != "AL") {
-                        trace!("ST header is not present");
-                        continue;
-                    }
+            let opened = (0..attempts)
+                .map(|attempt| search_sockets(&self.socket_builder, address, &interfaces, &discover_messages, attempt))
+                .collect::<Result<Vec<_>, _>>();
+            match opened {
+                Ok(opened) => sockets.extend(opened.into_iter().flatten()),
+                Err(e) if addresses.len() > 1 => {
+                    debug!("Can't search {:?}: {}", address, e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
 
-                    let new_service = ServiceDescription {
-                        usn_uri: String::from_utf8_lossy(usn.expect("Error with USN-Header").value)
-                            .into(),
-                        service_type_uri: String::from_utf8_lossy(
-                            st.expect("Error with ST-Header").value,
-                        )
-                        .into(),
-                        expiration: 100, // TODO needs to get parsed from max-age=<someint>
-                        location: String::from_utf8_lossy(al.expect("Error with AL-Header").value)
-                            .into(), // TODO is: <some:service><http://foo/bar> but should be http://foo/bar
-                    };
+        if let (true, Some(e)) = (sockets.is_empty(), last_error) {
+            return Err(e);
+        }
 
-                    let mut services_guard = self.services.lock().unwrap();
-                    let mut found = false;
+        Ok(sockets)
+    }
+    
+    /// Watch the network for Services announcing or withdrawing themselves
+    ///
+    /// Listens for multicasted `NOTIFY` messages in the given scope, keeps the list of Services up to date
+    /// and reports every change as a [ClientEvent].
+    /// Only Services this Client knows about are reported as updated, expired or gone.
+    ///
+    /// Watching stops once the returned receiver is dropped.
+    pub async fn watch(
+        &self,
+        address: MulticastAddr,
+    ) -> Result<Receiver<ClientEvent>, Error> {
+        let interfaces = resolve_interfaces(&self.interfaces)?;
+        let socket = Arc::new(self.socket_builder.bind_ssdp(&address)?);
+        if interfaces.is_empty() {
+            join_socket(&address, socket.clone(), None)?;
+        }
+        for interface in &interfaces {
+            join_socket(&address, socket.clone(), Some(interface))?;
+        }
 
-                    for service in services_guard.iter_mut() {
-                        if service.usn_uri == new_service.usn_uri {
-                            *service = new_service.clone();
-                            found = true;
-                            break;
+        let (events, receiver) = mpsc::channel(64);
+        let services = self.services.clone();
+
+        tokio::spawn(async move {
+            let mut buf = vec![0; 1024];
+            let mut expiry_check = interval(Duration::from_secs(1));
+
+            debug!("Start watching for SSDP notifications...");
+
+            'watch: loop {
+                let event = tokio::select! {
+                    _ = events.closed() => break,
+                    _ = expiry_check.tick() => {
+                        for service in evict_expired(&services) {
+                            if events.send(ClientEvent::ServiceExpired(service)).await.is_err() {
+                                break 'watch;
+                            }
                         }
+
+                        continue;
                     }
+                    received = socket.recv_from(&mut buf) => match received {
+                        Ok((len, addr)) => {
+                            trace!(
+                                "Received {} bytes from {}: {:#?}",
+                                len,
+                                addr,
+                                String::from_utf8_lossy(&buf[..len])
+                            );
+
+                            // The interface is told by the network the sender is in
+                            let interface = interfaces
+                                .iter()
+                                .find(|interface| interface.contains(addr.ip()))
+                                .map(|interface| interface.name.clone());
 
-                    if !found {
-                        services_guard.push(new_service);
+                            match SsdpMessage::parse(&buf[..len]) {
+                                Ok(message) => handle_notify(&services, message, addr, interface),
+                                Err(e) => {
+                                    trace!("Could not parse notification: {}", e);
+                                    continue;
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            trace!("Error receiving notification: {}", e);
+                            continue;
+                        }
                     }
+                };
 
-                    buf.clear();
-                    buf.resize(1024, 0);
-                }
-                Ok(Err(e)) => {
-                    trace!("Error receiving response: {}", e);
-                }
-                Err(_) => {
-                    break; // Timeout reached
+                let Some(event) = event else {
+                    continue;
+                };
+
+                if events.send(event).await.is_err() {
+                    break;
                 }
             }
-        }
 
-        debug!("Services found: {:#?}", self.services);
+            debug!("Stop watching for SSDP notifications...");
+        });
 
-        Ok(())
+        Ok(receiver)
     }
-    
+
     /// Retrieve a list of all Services that answered to our multicast call
+    ///
+    /// Services that expired are left out
     pub fn get_services(&self) -> Vec<ServiceDescription> {
-        self.services.lock().unwrap().clone()
+        self.get_discovered_services()
+            .into_iter()
+            .map(|service| service.description)
+            .collect()
+    }
+
+    /// Retrieve a list of all Services that answered to our multicast call, along with their expiry
+    ///
+    /// Services that expired are left out
+    pub fn get_discovered_services(&self) -> Vec<DiscoveredService> {
+        self.services
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|service| !service.is_expired())
+            .cloned()
+            .collect()
+    }
+
+    /// Removes all expired Services from the list and returns them
+    ///
+    /// Expired Services are never returned by [Client::get_services], this frees their memory.
+    /// [Client::watch] calls this regularly and reports the result as [ClientEvent::ServiceExpired].
+    pub fn evict_expired(&self) -> Vec<ServiceDescription> {
+        evict_expired(&self.services)
     }
 
     /// Changes the timeout
+    ///
+    /// [Client::discover] waits at least for the `MX` seconds Services may delay their answers, see [Client::set_mx].
     pub fn set_timeout(&mut self, timeout: Duration) -> &Self {
         self.timeout = timeout;
 
         self
     }
+
+    /// Changes the `MX` sent with `M-SEARCH`
+    ///
+    /// Services delay their answers by a random time of up to `mx` seconds, so the answers of many Services
+    /// don't arrive all at once. UPnP allows 1 to 5 seconds, defaults to `1`.
+    pub fn set_mx(&mut self, mx: u32) -> &Self {
+        self.mx = mx;
+
+        self
+    }
+
+    /// Changes how often each `M-SEARCH` is sent
+    ///
+    /// The first one is sent right away, the others are spread over the `MX` seconds, see [Client::set_mx].
+    /// Answers to all of them are merged by USN. Defaults to `2`, as UPnP recommends sending each search more than once.
+    pub fn set_search_count(&mut self, count: usize) -> &Self {
+        self.search_count = count.max(1);
+
+        self
+    }
+
+    /// The time to wait for answers: the timeout, but at least until `MX` plus some slack for the network
+    /// have passed since the last `M-SEARCH` of `schedule`
+    pub(crate) fn listen_timeout(&self, schedule: &[Duration]) -> Duration {
+        let last_search = schedule.last().copied().unwrap_or_default();
+
+        self.timeout
+            .max(last_search + Duration::from_secs(self.mx.into()) + Duration::from_millis(500))
+    }
+
+    /// Changes the flavour of SSDP used for searching
+    ///
+    /// Defaults to [Protocol::Draft]. Answers are understood in both flavours either way.
+    pub fn set_protocol(&mut self, protocol: Protocol) -> &Self {
+        self.protocol = protocol;
+
+        self
+    }
+
+    /// Changes the `USER-AGENT` sent in [Protocol::Upnp] mode
+    ///
+    /// UPnP expects `<OS>/<version> UPnP/2.0 <product>/<version>`
+    pub fn set_user_agent(&mut self, user_agent: String) -> &Self {
+        self.user_agent = user_agent;
+
+        self
+    }
+
+    /// Changes the network interfaces to search and watch on
+    ///
+    /// [Client::discover] sends its `M-SEARCH` out on each of them, [Client::watch] joins the multicast group on each of them.
+    /// Found Services tell the interface they were seen on in [DiscoveredService::interface].
+    /// Defaults to none, letting the OS choose a single interface.
+    pub fn set_interfaces(&mut self, interfaces: Vec<Interface>) -> &Self {
+        self.interfaces = interfaces;
+
+        self
+    }
+
+    /// Changes the multicast TTL (`IP_MULTICAST_TTL`) or IPv6 hop limit (`IPV6_MULTICAST_HOPS`)
+    ///
+    /// Limits how many routers multicasts cross. Defaults to `2` as SSDP requires.
+    pub fn set_multicast_ttl(&mut self, ttl: u32) -> &Self {
+        self.socket_builder.multicast_ttl = Some(ttl);
+
+        self
+    }
+
+    /// Changes whether multicasts are looped back to this host (`IP_MULTICAST_LOOP`, `IPV6_MULTICAST_LOOP`)
+    ///
+    /// Defaults to `true`, so Services on the same host see them.
+    pub fn set_multicast_loop(&mut self, multicast_loop: bool) -> &Self {
+        self.socket_builder.multicast_loop = Some(multicast_loop);
+
+        self
+    }
+
+    /// Changes the receive buffer size (`SO_RCVBUF`) of the sockets
+    ///
+    /// Defaults to the OS default. Raise it if many answers arrive at once.
+    pub fn set_recv_buffer_size(&mut self, size: usize) -> &Self {
+        self.socket_builder.recv_buffer_size = Some(size);
+
+        self
+    }
+
+    /// Changes the send buffer size (`SO_SNDBUF`) of the sockets
+    ///
+    /// Defaults to the OS default.
+    pub fn set_send_buffer_size(&mut self, size: usize) -> &Self {
+        self.socket_builder.send_buffer_size = Some(size);
+
+        self
+    }
 }
@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+
+use crate::client::search_schedule;
+use crate::client::Client;
+use crate::client::search_targets;
+use crate::service::ServiceDescription;
+use crate::Error;
+use crate::MulticastAddr;
+
+#[test]
+fn test_search_schedule_single() {
+    assert_eq!(vec![Duration::ZERO], search_schedule(1, 3));
+    assert_eq!(vec![Duration::ZERO], search_schedule(0, 3));
+}
+
+#[test]
+fn test_search_schedule_spread_over_mx() {
+    for _ in 0..100 {
+        let schedule = search_schedule(3, 3);
+
+        assert_eq!(3, schedule.len());
+        assert_eq!(Duration::ZERO, schedule[0]);
+        assert!(schedule[1] >= Duration::from_secs(1) && schedule[1] < Duration::from_millis(1500));
+        assert!(schedule[2] >= Duration::from_secs(2) && schedule[2] < Duration::from_millis(2500));
+    }
+}
+
+#[test]
+fn test_search_schedule_without_mx() {
+    assert_eq!(vec![Duration::ZERO; 3], search_schedule(3, 0));
+}
+
+#[test]
+fn test_search_targets() {
+    let description = ServiceDescription {
+        usn_uri: "uuid:some-service-uuid".to_string(),
+        service_type_uri: "some:special:service".to_string(),
+        expiration: 100,
+        location: "https://foo/bar".to_string(),
+    };
+
+    let searches = vec!["ssdp:all".to_string(), "some:special:service".to_string()];
+    assert_eq!(vec![&searches[0], &searches[1]], search_targets(&searches, &description));
+
+    let searches = vec!["some:other:service".to_string(), "ssdp:all".to_string()];
+    assert_eq!(vec![&searches[1]], search_targets(&searches, &description));
+
+    let searches = vec!["some:other:service".to_string()];
+    assert!(search_targets(&searches, &description).is_empty());
+}
+
+#[tokio::test]
+async fn test_discover_without_scopes() {
+    let result = Client::default()
+        .discover_scopes("uuid:some-client-uuid".to_string(), &[], "ssdp:all".to_string())
+        .await;
+
+    assert!(matches!(result, Err(Error::EmptySearch)));
+}
+
+#[tokio::test]
+async fn test_discover_without_targets() {
+    let result = Client::default()
+        .discover_targets("uuid:some-client-uuid".to_string(), MulticastAddr::Loopback, vec![])
+        .await;
+
+    assert!(matches!(result, Err(Error::EmptySearch)));
+}
+
+#[tokio::test]
+async fn test_discover_until_without_timeout() {
+    let mut client = Client::default();
+    client.set_timeout(Duration::MAX);
+
+    let result = tokio::time::timeout(
+        Duration::from_secs(1),
+        client.discover_until(
+            "uuid:some-client-uuid".to_string(),
+            MulticastAddr::Loopback,
+            "some:special:service".to_string(),
+            |_| true,
+        ),
+    )
+    .await;
+
+    assert!(matches!(result, Ok(Ok(_))));
+}
+
+#[test]
+fn test_listen_timeout_covers_last_search() {
+    let mut client = Client::default();
+    client.set_mx(5);
+
+    // Answers to the last M-SEARCH may take the whole MX
+    assert_eq!(
+        Duration::from_millis(8700),
+        client.listen_timeout(&[Duration::ZERO, Duration::from_millis(3200)])
+    );
+    assert_eq!(Duration::from_millis(5500), client.listen_timeout(&[Duration::ZERO]));
+
+    client.set_mx(1);
+    assert_eq!(Duration::from_secs(5), client.listen_timeout(&[Duration::ZERO, Duration::from_millis(600)]));
+}
+
+#[tokio::test]
+async fn test_search_unicast_malformed_answer() {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let address = socket.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buf = vec![0; 1024];
+        let (_, client) = socket.recv_from(&mut buf).await.unwrap();
+        // No USN
+        let answer = "HTTP/1.1 200 OK\r\nST: some:special:service\r\nLOCATION: https://foo/bar\r\n\r\n";
+        socket.send_to(answer.as_bytes(), client).await.unwrap();
+    });
+
+    let mut client = Client::default();
+    client.set_timeout(Duration::from_millis(500));
+    let result = client
+        .search_unicast("uuid:some-client-uuid".to_string(), address, "some:special:service".to_string())
+        .await;
+
+    assert!(matches!(result, Err(Error::Parse(_))));
+}
@@ -0,0 +1,160 @@
+use crate::service::ServiceDescription;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// A UPnP root device
+///
+/// UPnP control points don't look for single services but for devices, each announcing a fixed set of
+/// notification types. [Device::service_descriptions] fans the device tree out into the
+/// [ServiceDescription]s a [crate::service::Service] announces, see [crate::service::Service::add_device].
+pub struct Device {
+    /// Unique Device Name
+    ///
+    /// ```text
+    /// uuid:83760048-2d32-4e48-854f-f63a8fa9fd09
+    /// ```
+    pub udn: String,
+
+    /// The UPnP device type
+    ///
+    /// ```text
+    /// urn:schemas-upnp-org:device:MediaServer:1
+    /// ```
+    pub device_type: String,
+
+    /// Cache-Control max age, shared by the whole device tree
+    pub expiration: u32,
+
+    /// Location of the device description, shared by the whole device tree
+    ///
+    /// ```text
+    /// http://192.168.1.10:8200/rootDesc.xml
+    /// ```
+    pub location: String,
+
+    /// The UPnP service types of this device
+    ///
+    /// ```text
+    /// urn:schemas-upnp-org:service:ContentDirectory:1
+    /// ```
+    pub service_types: Vec<String>,
+
+    /// Devices embedded into this device
+    pub embedded_devices: Vec<EmbeddedDevice>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// A device embedded into a [Device]
+///
+/// Embedded devices are announced with the location and expiration of their root [Device].
+pub struct EmbeddedDevice {
+    /// Unique Device Name, see [Device::udn](Device#structfield.udn)
+    pub udn: String,
+
+    /// The UPnP device type, see [Device::device_type](Device#structfield.device_type)
+    pub device_type: String,
+
+    /// The UPnP service types of this device, see [Device::service_types](Device#structfield.service_types)
+    pub service_types: Vec<String>,
+
+    /// Devices embedded into this device
+    pub embedded_devices: Vec<EmbeddedDevice>,
+}
+
+/// The parts of [Device] and [EmbeddedDevice] fanned out into [ServiceDescription]s
+trait DeviceNode {
+    fn udn(&self) -> &str;
+    fn device_type(&self) -> &str;
+    fn service_types(&self) -> &[String];
+    fn embedded_devices(&self) -> &[EmbeddedDevice];
+}
+
+impl DeviceNode for Device {
+    fn udn(&self) -> &str {
+        &self.udn
+    }
+
+    fn device_type(&self) -> &str {
+        &self.device_type
+    }
+
+    fn service_types(&self) -> &[String] {
+        &self.service_types
+    }
+
+    fn embedded_devices(&self) -> &[EmbeddedDevice] {
+        &self.embedded_devices
+    }
+}
+
+impl DeviceNode for EmbeddedDevice {
+    fn udn(&self) -> &str {
+        &self.udn
+    }
+
+    fn device_type(&self) -> &str {
+        &self.device_type
+    }
+
+    fn service_types(&self) -> &[String] {
+        &self.service_types
+    }
+
+    fn embedded_devices(&self) -> &[EmbeddedDevice] {
+        &self.embedded_devices
+    }
+}
+
+impl Device {
+    /// Returns all notification types this device tree announces, with their USN
+    ///
+    /// As UPnP requires, these are:
+    /// - `upnp:rootdevice` with USN `uuid:<root>::upnp:rootdevice`
+    /// - `uuid:<device>` with USN `uuid:<device>`, for each device
+    /// - The device type with USN `uuid:<device>::<device type>`, for each device
+    /// - Each service type with USN `uuid:<device>::<service type>`, once per device
+    pub fn service_descriptions(&self) -> Vec<ServiceDescription> {
+        let mut descriptions = vec![self.describe(
+            "upnp:rootdevice".to_string(),
+            format!("{}::upnp:rootdevice", self.udn),
+        )];
+
+        self.fan_out(self, &mut descriptions);
+
+        descriptions
+    }
+
+    /// Adds the descriptions of one device and, recursively, its embedded devices
+    fn fan_out(&self, device: &impl DeviceNode, descriptions: &mut Vec<ServiceDescription>) {
+        let udn = device.udn();
+        descriptions.push(self.describe(udn.to_string(), udn.to_string()));
+        descriptions.push(self.describe(
+            device.device_type().to_string(),
+            format!("{}::{}", udn, device.device_type()),
+        ));
+
+        let service_types = device.service_types();
+        for (index, service_type) in service_types.iter().enumerate() {
+            if service_types[..index].contains(service_type) {
+                continue;
+            }
+
+            descriptions.push(self.describe(
+                service_type.clone(),
+                format!("{}::{}", udn, service_type),
+            ));
+        }
+
+        for embedded_device in device.embedded_devices() {
+            self.fan_out(embedded_device, descriptions);
+        }
+    }
+
+    fn describe(&self, service_type_uri: String, usn_uri: String) -> ServiceDescription {
+        ServiceDescription {
+            usn_uri,
+            service_type_uri,
+            expiration: self.expiration,
+            location: self.location.clone(),
+        }
+    }
+}
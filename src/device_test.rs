@@ -0,0 +1,110 @@
+use crate::device::Device;
+use crate::device::EmbeddedDevice;
+use crate::service::Service;
+use crate::service::ServiceDescription;
+
+fn media_server() -> Device {
+    Device {
+        udn: "uuid:root".to_string(),
+        device_type: "urn:schemas-upnp-org:device:MediaServer:1".to_string(),
+        expiration: 1800,
+        location: "http://192.168.1.10:8200/rootDesc.xml".to_string(),
+        service_types: vec![
+            "urn:schemas-upnp-org:service:ContentDirectory:1".to_string(),
+            "urn:schemas-upnp-org:service:ConnectionManager:1".to_string(),
+        ],
+        embedded_devices: vec![EmbeddedDevice {
+            udn: "uuid:embedded".to_string(),
+            device_type: "urn:schemas-upnp-org:device:Printer:1".to_string(),
+            service_types: vec![
+                "urn:schemas-upnp-org:service:ConnectionManager:1".to_string(),
+                "urn:schemas-upnp-org:service:ConnectionManager:1".to_string(),
+            ],
+            embedded_devices: vec![],
+        }],
+    }
+}
+
+#[test]
+fn test_device_fan_out() {
+    let descriptions = media_server().service_descriptions();
+
+    let announced: Vec<(&str, &str)> = descriptions
+        .iter()
+        .map(|description| (description.service_type_uri.as_str(), description.usn_uri.as_str()))
+        .collect();
+
+    assert_eq!(
+        vec![
+            ("upnp:rootdevice", "uuid:root::upnp:rootdevice"),
+            ("uuid:root", "uuid:root"),
+            (
+                "urn:schemas-upnp-org:device:MediaServer:1",
+                "uuid:root::urn:schemas-upnp-org:device:MediaServer:1"
+            ),
+            (
+                "urn:schemas-upnp-org:service:ContentDirectory:1",
+                "uuid:root::urn:schemas-upnp-org:service:ContentDirectory:1"
+            ),
+            (
+                "urn:schemas-upnp-org:service:ConnectionManager:1",
+                "uuid:root::urn:schemas-upnp-org:service:ConnectionManager:1"
+            ),
+            ("uuid:embedded", "uuid:embedded"),
+            (
+                "urn:schemas-upnp-org:device:Printer:1",
+                "uuid:embedded::urn:schemas-upnp-org:device:Printer:1"
+            ),
+            (
+                "urn:schemas-upnp-org:service:ConnectionManager:1",
+                "uuid:embedded::urn:schemas-upnp-org:service:ConnectionManager:1"
+            ),
+        ],
+        announced
+    );
+
+    for description in descriptions {
+        assert_eq!(1800, description.expiration);
+        assert_eq!("http://192.168.1.10:8200/rootDesc.xml", description.location);
+    }
+}
+
+#[tokio::test]
+async fn test_service_from_device() {
+    let device = media_server();
+
+    let service = Service::from_device(&device);
+    assert_eq!(device.service_descriptions(), service.get_services());
+}
+
+#[tokio::test]
+async fn test_service_add_and_remove_device() {
+    let device = media_server();
+    let other = ServiceDescription {
+        usn_uri: "uuid:other".to_string(),
+        service_type_uri: "some:other:service".to_string(),
+        expiration: 100,
+        location: "https://foo/bar".to_string(),
+    };
+    let service = Service::new(other.clone());
+
+    service.add_device(&device).await.unwrap();
+    let usns: Vec<String> = service.get_services().into_iter().map(|registered| registered.usn_uri).collect();
+    assert_eq!(
+        vec![
+            "uuid:other",
+            "uuid:root::upnp:rootdevice",
+            "uuid:root",
+            "uuid:root::urn:schemas-upnp-org:device:MediaServer:1",
+            "uuid:root::urn:schemas-upnp-org:service:ContentDirectory:1",
+            "uuid:root::urn:schemas-upnp-org:service:ConnectionManager:1",
+            "uuid:embedded",
+            "uuid:embedded::urn:schemas-upnp-org:device:Printer:1",
+            "uuid:embedded::urn:schemas-upnp-org:service:ConnectionManager:1",
+        ],
+        usns
+    );
+
+    service.remove_device(&device).await.unwrap();
+    assert_eq!(vec![other], service.get_services());
+}
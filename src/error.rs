@@ -0,0 +1,65 @@
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+
+use crate::message::ParseError;
+
+#[derive(Debug)]
+/// Everything that can go wrong while running a [crate::service::Service] or [crate::client::Client]
+pub enum Error {
+    /// A socket could not be created or bound, e.g. because another program owns the SSDP port
+    Bind(io::Error),
+
+    /// The multicast group could not be joined or the interface for outgoing multicasts could not be set
+    Join(io::Error),
+
+    /// A network interface does not exist or lacks an address of the required IP version
+    Interface(String),
+
+    /// A message could not be sent
+    Send(io::Error),
+
+    /// A message could not be received
+    Receive(io::Error),
+
+    /// A received message is not valid SSDP
+    Parse(ParseError),
+
+    /// No answer arrived in time
+    Timeout,
+
+    /// A search was started without anything to search, like an empty list of scopes
+    EmptySearch,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Bind(e) => write!(f, "could not bind socket: {}", e),
+            Error::Join(e) => write!(f, "could not join multicast group: {}", e),
+            Error::Interface(e) => write!(f, "unusable network interface: {}", e),
+            Error::Send(e) => write!(f, "could not send message: {}", e),
+            Error::Receive(e) => write!(f, "could not receive message: {}", e),
+            Error::Parse(e) => write!(f, "could not parse message: {}", e),
+            Error::Timeout => write!(f, "timed out"),
+            Error::EmptySearch => write!(f, "nothing to search"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Bind(e) | Error::Join(e) | Error::Send(e) | Error::Receive(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Interface(_) | Error::Timeout | Error::EmptySearch => None,
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
@@ -1,84 +1,314 @@
+use std::net::SocketAddr;
+
+use log::trace;
+
+use crate::message::ParseError;
+use crate::message::SsdpMessage;
 use crate::service::ServiceDescription;
 use crate::MulticastAddr;
-use crate::SSDP_PORT;
 
-#[allow(dead_code)]
-// TODO remove allow(dead_code) when this is implemented
+/// The headers of a received SSDP message
+///
+/// Headers are looked up by name, case-insensitive and with surrounding whitespace removed,
+/// so it does not matter in which order or spelling a peer sends them.
+pub(crate) struct Headers(Vec<(String, String)>);
+
+impl Headers {
+    pub(crate) fn new(headers: &[httparse::Header]) -> Self {
+        Headers(
+            headers
+                .iter()
+                .map(|header| {
+                    (
+                        header.name.trim().to_string(),
+                        String::from_utf8_lossy(header.value).trim().to_string(),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the value of the first header called `name`
+    pub(crate) fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The UPnP headers a [crate::service::Service] sends in [crate::Protocol::Upnp] mode
+pub(crate) struct UpnpHeaders<'a> {
+    /// `SERVER`
+    pub(crate) server: &'a str,
+    /// `BOOTID.UPNP.ORG`
+    pub(crate) boot_id: u32,
+    /// `CONFIGID.UPNP.ORG`
+    pub(crate) config_id: u32,
+    /// `SEARCHPORT.UPNP.ORG`, only sent if unicast `M-SEARCH` are not received on the SSDP port
+    pub(crate) search_port: Option<u16>,
+}
+
+/// The product token sent in `SERVER` and `USER-AGENT` unless configured otherwise
+///
+/// UPnP expects `<OS>/<version> UPnP/2.0 <product>/<version>`
+pub(crate) fn default_product_token() -> String {
+    format!(
+        "{} UPnP/2.0 {}/{}",
+        std::env::consts::OS,
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
 /// Generates ssdp:alive
 ///
 /// This should be multicasted when a [crate::service::Service] is starting.
+///
+/// - `upnp` - The UPnP headers to send instead of the draft `AL` header, if any
 pub(crate) fn generate_ssdp_alive(
     service_description: &ServiceDescription,
     ssdp_multicast_addr: &MulticastAddr,
+    upnp: Option<&UpnpHeaders>,
 ) -> String {
-    format!(
-        "NOTIFY * HTTP/1.1\r\nHost: {}:{}\r\nNT: {}\r\nNTS: ssdp:alive\r\nUSN: {}\r\nAL: <{}><{}>\r\nCache-Control: max-age = {}\r\n\r\n",
-        ssdp_multicast_addr.get_ip(),
-        SSDP_PORT,
-        service_description.service_type_uri,
-        service_description.usn_uri,
-        service_description.usn_uri,
-        service_description.location,
-        service_description.expiration,
-    )
+    let (al, location) = locations_for(service_description, &service_description.usn_uri, upnp);
+
+    SsdpMessage::NotifyAlive {
+        host: ssdp_multicast_addr.socket_addr().to_string(),
+        nt: service_description.service_type_uri.clone(),
+        usn: service_description.usn_uri.clone(),
+        al,
+        location,
+        cache_control: Some(format!("max-age = {}", service_description.expiration)),
+        server: upnp.map(|upnp| upnp.server.to_string()),
+        boot_id: upnp.map(|upnp| upnp.boot_id),
+        config_id: upnp.map(|upnp| upnp.config_id),
+        search_port: upnp.and_then(|upnp| upnp.search_port),
+    }
+    .to_string()
 }
 
-#[allow(dead_code)]
-// TODO remove allow(dead_code) when this is implemented
 /// Generates ssdp:byebye
 ///
 /// This should be multicasted when a [crate::service::Service] is stopping.
+///
+/// - `upnp` - The UPnP headers to send, if any
 pub(crate) fn generate_ssdp_byebye(
     service_description: &ServiceDescription,
     ssdp_multicast_addr: &MulticastAddr,
+    upnp: Option<&UpnpHeaders>,
 ) -> String {
-    format!(
-        "NOTIFY * HTTP/1.1\r\nHost: {}:{}\r\nNT: {}\r\nNTS: ssdp:byebye\r\nUSN: {}\r\n\r\n",
-        ssdp_multicast_addr.get_ip(),
-        SSDP_PORT,
-        service_description.service_type_uri,
-        service_description.usn_uri,
-    )
+    SsdpMessage::NotifyByebye {
+        host: ssdp_multicast_addr.socket_addr().to_string(),
+        nt: service_description.service_type_uri.clone(),
+        usn: service_description.usn_uri.clone(),
+        boot_id: upnp.map(|upnp| upnp.boot_id),
+        config_id: upnp.map(|upnp| upnp.config_id),
+    }
+    .to_string()
 }
 
 /// Answer to a `M-SEARCH` request
 ///
 /// - `service_description` - The descriptive object of the [crate::service::Service]
-/// - `s` - The unique identifier of the requesting [crate::client::Client]
+/// - `s` - The unique identifier of the requesting [crate::client::Client], if it sent one
+/// - `upnp` - The UPnP headers to send instead of the draft `AL` header, if any
 pub(crate) fn generate_ssdp_discover_answer(
     service_description: &ServiceDescription,
-    s: String
+    s: Option<String>,
+    upnp: Option<&UpnpHeaders>,
 ) -> String {
-    format!(
-        "HTTP/1.1 200 OK\r\nS: {}\r\nExt: \r\nCache-Control: no-cache=\"Ext\", max-age={}\r\nST: {}\r\nUSN: {}\r\nAL: <{}><{}>\r\n\r\n",
+    let (al, location) = locations_for(
+        service_description,
+        &service_description.service_type_uri,
+        upnp,
+    );
+
+    SsdpMessage::SearchResponse {
         s,
-        service_description.expiration,
-        service_description.service_type_uri,
-        service_description.usn_uri,
-        service_description.service_type_uri,
-        service_description.location,
-    )
+        cache_control: Some(format!("no-cache=\"Ext\", max-age={}", service_description.expiration)),
+        st: service_description.service_type_uri.clone(),
+        usn: service_description.usn_uri.clone(),
+        al,
+        location,
+        server: upnp.map(|upnp| upnp.server.to_string()),
+        boot_id: upnp.map(|upnp| upnp.boot_id),
+        config_id: upnp.map(|upnp| upnp.config_id),
+        search_port: upnp.and_then(|upnp| upnp.search_port),
+    }
+    .to_string()
+}
+
+/// The draft sends the location as `AL: <uri><location>`, UPnP as `LOCATION: location`
+fn locations_for(
+    service_description: &ServiceDescription,
+    uri: &str,
+    upnp: Option<&UpnpHeaders>,
+) -> (Vec<String>, Option<String>) {
+    match upnp {
+        Some(_) => (vec![], Some(service_description.location.clone())),
+        None => (
+            vec![uri.to_string(), service_description.location.clone()],
+            None,
+        ),
+    }
+}
+
+/// Expiration assumed for Services not sending a `max-age`
+///
+/// UPnP requires at least 1800 seconds
+pub(crate) static DEFAULT_EXPIRATION: u32 = 1800;
+
+/// Extracts `max-age` from a `Cache-Control` header
+///
+/// Directives are separated by commas outside of quotes, names are case-insensitive and the value may be
+/// quoted or surrounded by whitespace, so e.g. `no-cache="Ext", max-age = 1800` yields `1800`.
+pub(crate) fn parse_max_age(cache_control: &str) -> Option<u32> {
+    let mut directives = vec![];
+    let mut directive = String::new();
+    let mut quoted = false;
+
+    for c in cache_control.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                directive.push(c);
+            }
+            ',' if !quoted => directives.push(std::mem::take(&mut directive)),
+            _ => directive.push(c),
+        }
+    }
+    directives.push(directive);
+
+    directives.iter().find_map(|directive| {
+        let (name, value) = directive.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("max-age") {
+            return None;
+        }
+
+        value.trim().trim_matches('"').parse().ok()
+    })
 }
 
 // Helper for the Client
 /// Generates a `M-SEARCH` request to find [crate::service::Service] within the given multicast network
 ///
-/// - `s` - The unique identifier of this [crate::client::Client], mostly an uuid e.g. `uuid:83760048-2d32-4e48-854f-f63a8fa9fd09`
+/// - `s` - The unique identifier of this [crate::client::Client], mostly an uuid e.g. `uuid:83760048-2d32-4e48-854f-f63a8fa9fd09`.
+///   UPnP doesn't know this header.
 /// - `st` - A name to search for, can be `ssdp:all` to find all services or a more specific phrase like `my:service`
 /// - `ssdp_multicast_addr` - The multicast network to announce this search request
+/// - `mx` - The maximum number of seconds a [crate::service::Service] may delay its answer
+/// - `user_agent` - The UPnP `USER-AGENT` header, if any
 pub(crate) fn generate_ssdp_discover(
-    s: String,
+    s: Option<String>,
     st: String,
     ssdp_multicast_addr: &MulticastAddr,
+    mx: u32,
+    user_agent: Option<String>,
 ) -> String {
-    // TODO what even is this MX header???
-    // TODO error in RFC? MAN is the only quoted parameter
-    format!(
-        "M-SEARCH * HTTP/1.1\r\nS: {}\r\nHost: {}:{}\r\nMAN: \"ssdp:discover\"\r\nST: {}\r\nMX: {}\r\n\r\n",
+    SsdpMessage::MSearch {
         s,
-        ssdp_multicast_addr.get_ip(),
-        SSDP_PORT,
+        host: ssdp_multicast_addr.socket_addr().to_string(),
         st,
-        1,
-    )
+        mx: Some(mx),
+        user_agent,
+    }
+    .to_string()
+}
+
+/// Generates a `M-SEARCH` request sent directly to a single [crate::service::Service]
+///
+/// Unlike [generate_ssdp_discover], `HOST` is the address of the Service and `MX` is left out, so it answers right away.
+///
+/// - `s` - The unique identifier of this [crate::client::Client], UPnP doesn't know this header.
+/// - `st` - A name to search for, can be `ssdp:all` to find all services or a more specific phrase like `my:service`
+/// - `host` - The address of the Service
+/// - `user_agent` - The UPnP `USER-AGENT` header, if any
+pub(crate) fn generate_ssdp_unicast_discover(
+    s: Option<String>,
+    st: String,
+    host: SocketAddr,
+    user_agent: Option<String>,
+) -> String {
+    SsdpMessage::MSearch {
+        s,
+        host: host.to_string(),
+        st,
+        mx: None,
+        user_agent,
+    }
+    .to_string()
+}
+
+/// Splits an `AL` header into its URIs
+///
+/// `<my:service><https://foo/bar>` yields `my:service` and `https://foo/bar`.
+/// A value without any brackets is taken as a single URI.
+pub(crate) fn parse_al(al: &str) -> Vec<String> {
+    let al = al.trim();
+    if !al.contains('<') {
+        return if al.is_empty() { vec![] } else { vec![al.to_string()] };
+    }
+
+    al.split('<')
+        .filter_map(|part| part.split_once('>'))
+        .map(|(uri, _)| uri.trim().to_string())
+        .filter(|uri| !uri.is_empty())
+        .collect()
+}
+
+/// Collects the URLs a Service can be reached at, the preferred one first
+///
+/// The UPnP `LOCATION` header is preferred, followed by all URLs within `AL`.
+/// Other URIs within `AL`, like the leading service type in `<my:service><https://foo/bar>`, are left out,
+/// unless `AL` holds nothing else.
+pub(crate) fn locations(al: &[String], location: Option<&str>) -> Vec<String> {
+    let mut locations: Vec<String> = location.into_iter().map(str::to_string).collect();
+
+    for uri in al.iter().filter(|uri| uri.contains("://")) {
+        if !locations.contains(uri) {
+            locations.push(uri.clone());
+        }
+    }
+
+    if locations.is_empty() {
+        locations.extend(al.last().cloned());
+    }
+
+    locations
+}
+
+/// Parses the answer to a `M-SEARCH` request
+///
+/// Returns `None` for other valid SSDP messages, like the `NOTIFY` of Services in the group searched,
+/// and an error if `buf` is not a `200 OK` carrying the `USN`, `ST` and `AL` or `LOCATION` headers.
+pub(crate) fn parse_ssdp_discover_answer(buf: &[u8]) -> Result<Option<SsdpMessage>, ParseError> {
+    match SsdpMessage::parse(buf) {
+        Ok(message @ SsdpMessage::SearchResponse { .. }) => Ok(Some(message)),
+        Ok(_) => {
+            trace!("Message is not a M-SEARCH answer");
+            Ok(None)
+        }
+        Err(e) => {
+            trace!("Could not parse M-SEARCH answer: {}", e);
+            Err(e)
+        }
+    }
+}
+
+/// Parses a `M-SEARCH` request
+///
+/// Returns the `S`, `ST` and `MX` headers of the request or `None` if `buf` is not a valid `M-SEARCH`
+pub(crate) fn parse_ssdp_discover(buf: &[u8]) -> Option<(Option<String>, String, Option<u32>)> {
+    match SsdpMessage::parse(buf) {
+        Ok(SsdpMessage::MSearch { s, st, mx, .. }) => Some((s, st, mx)),
+        Ok(_) => {
+            trace!("Request is not M-SEARCH");
+            None
+        }
+        Err(e) => {
+            trace!("Could not parse M-SEARCH request: {}", e);
+            None
+        }
+    }
 }
@@ -1,10 +1,19 @@
 use crate::http_helper::{generate_ssdp_alive, generate_ssdp_discover_answer};
 use crate::http_helper::generate_ssdp_byebye;
 use crate::http_helper::generate_ssdp_discover;
+use crate::http_helper::generate_ssdp_unicast_discover;
+use crate::http_helper::Headers;
+use crate::http_helper::UpnpHeaders;
+use crate::http_helper::{locations, parse_al, parse_max_age, parse_ssdp_discover, parse_ssdp_discover_answer};
+use crate::client::DiscoveredService;
 use crate::service::ServiceDescription;
 use crate::MulticastAddr;
 use crate::SSDP_PORT;
 
+fn parse_answer(buf: &[u8]) -> Option<DiscoveredService> {
+    parse_ssdp_discover_answer(buf).ok().flatten().and_then(DiscoveredService::from_message)
+}
+
 #[test]
 fn test_alive() {
     let alive = generate_ssdp_alive(
@@ -15,6 +24,7 @@ fn test_alive() {
             location: "https://foo/bar".to_string(),
         },
         &MulticastAddr::V4,
+        None,
     );
     let buf = alive.as_bytes();
 
@@ -25,7 +35,7 @@ fn test_alive() {
 
     assert_eq!("NOTIFY", req.method.unwrap());
 
-    let host = req.headers.get(0);
+    let host = req.headers.first();
     assert!(host.is_some());
     assert_eq!(
         format!("{}:{}", MulticastAddr::V4.get_ip(), SSDP_PORT,),
@@ -75,6 +85,7 @@ fn test_byebye() {
             location: "https://foo/bar".to_string(),
         },
         &MulticastAddr::V4,
+        None,
     );
     let buf = byebye.as_bytes();
 
@@ -85,7 +96,7 @@ fn test_byebye() {
 
     assert_eq!("NOTIFY", req.method.unwrap());
 
-    let host = req.headers.get(0);
+    let host = req.headers.first();
     assert!(host.is_some());
     assert_eq!(
         format!("{}:{}", MulticastAddr::V4.get_ip(), SSDP_PORT,),
@@ -120,7 +131,8 @@ fn test_discover_answer() {
             expiration: 42,
             location: "https://foo/bar".to_string(),
         },
-        "uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a".to_string(),
+        Some("uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a".to_string()),
+        None,
     );
     let buf = discover_answer.as_bytes();
 
@@ -131,7 +143,7 @@ fn test_discover_answer() {
 
     assert_eq!(200, resp.code.unwrap());
 
-    let s = resp.headers.get(0);
+    let s = resp.headers.first();
     assert!(s.is_some());
     assert_eq!("uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a", String::from_utf8_lossy(s.unwrap().value)
     );
@@ -167,12 +179,30 @@ fn test_discover_answer() {
     );
 }
 
+#[test]
+fn test_unicast_discover() {
+    let discover = generate_ssdp_unicast_discover(
+        None,
+        "my:service".to_string(),
+        "192.168.1.10:1900".parse().unwrap(),
+        Some("Linux/6.1 UPnP/2.0 test/1.0".to_string()),
+    );
+
+    let (s, st, mx) = parse_ssdp_discover(discover.as_bytes()).unwrap();
+    assert_eq!(None, s);
+    assert_eq!("my:service", st);
+    assert_eq!(None, mx);
+    assert!(discover.contains("192.168.1.10:1900"));
+}
+
 #[test]
 fn test_discover() {
     let discover = generate_ssdp_discover(
-        "uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a".to_string(),
+        Some("uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a".to_string()),
         "my:service".to_string(),
         &MulticastAddr::V4,
+        1,
+        None,
     );
     let buf = discover.as_bytes();
 
@@ -183,7 +213,7 @@ fn test_discover() {
 
     assert_eq!("M-SEARCH", req.method.unwrap());
 
-    let s = req.headers.get(0);
+    let s = req.headers.first();
     assert!(s.is_some());
     assert_eq!("uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a", String::from_utf8_lossy(s.unwrap().value)
     );
@@ -214,3 +244,369 @@ fn test_discover() {
         String::from_utf8_lossy(mx.unwrap().value)
     );
 }
+
+#[test]
+fn test_parse_discover_answer_roundtrip() {
+    let desc = ServiceDescription {
+        usn_uri: "uuid:83760048-2d32-4e48-854f-f63a8fa9fd09".to_string(),
+        service_type_uri: "my:service".to_string(),
+        expiration: 100,
+        location: "https://foo/bar".to_string(),
+    };
+    let answer = generate_ssdp_discover_answer(&desc, Some("uuid:some-client".to_string()), None);
+
+    let parsed = parse_answer(answer.as_bytes()).unwrap();
+
+    assert_eq!(desc, parsed.description);
+    assert_eq!(vec!["https://foo/bar".to_string()], parsed.locations);
+    assert_eq!(None, parsed.server);
+}
+
+#[test]
+fn test_parse_discover_answer_reordered_mixed_case() {
+    // Header order and spelling as sent by a media renderer in the wild
+    let answer = "HTTP/1.1 200 OK\r\n\
+        al: <urn:schemas-upnp-org:device:MediaRenderer:1><http://192.168.1.20:1400/xml/device_description.xml>\r\n\
+        CACHE-CONTROL: max-age = 1800\r\n\
+        EXT:\r\n\
+        Usn:   uuid:RINCON_000E58A0B1C201400::urn:schemas-upnp-org:device:MediaRenderer:1  \r\n\
+        SERVER: Linux UPnP/1.0 Sonos/70.3-35220 (ZPS1)\r\n\
+        st: urn:schemas-upnp-org:device:MediaRenderer:1\r\n\r\n";
+
+    let parsed = parse_answer(answer.as_bytes()).unwrap().description;
+
+    assert_eq!(
+        "uuid:RINCON_000E58A0B1C201400::urn:schemas-upnp-org:device:MediaRenderer:1",
+        parsed.usn_uri
+    );
+    assert_eq!("urn:schemas-upnp-org:device:MediaRenderer:1", parsed.service_type_uri);
+    assert_eq!(1800, parsed.expiration);
+    assert_eq!(
+        "http://192.168.1.20:1400/xml/device_description.xml",
+        parsed.location
+    );
+}
+
+#[test]
+fn test_parse_discover_answer_missing_header() {
+    let answer = "HTTP/1.1 200 OK\r\nST: my:service\r\nAL: <my:service><https://foo/bar>\r\n\r\n";
+
+    assert!(parse_answer(answer.as_bytes()).is_none());
+}
+
+#[test]
+fn test_parse_discover_answer_not_ok() {
+    let answer = "HTTP/1.1 404 Not Found\r\nST: my:service\r\nUSN: uuid:foo\r\nAL: <my:service><https://foo/bar>\r\n\r\n";
+
+    assert!(parse_answer(answer.as_bytes()).is_none());
+}
+
+#[test]
+fn test_parse_discover_roundtrip() {
+    let discover = generate_ssdp_discover(
+        Some("uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a".to_string()),
+        "my:service".to_string(),
+        &MulticastAddr::V4,
+        1,
+        None,
+    );
+
+    let (s, st, mx) = parse_ssdp_discover(discover.as_bytes()).unwrap();
+
+    assert_eq!(Some("uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a".to_string()), s);
+    assert_eq!("my:service", st);
+    assert_eq!(Some(1), mx);
+}
+
+#[test]
+fn test_parse_discover_reordered_mixed_case() {
+    let discover = "M-SEARCH * HTTP/1.1\r\n\
+        mx: 3\r\n\
+        st:  my:service\r\n\
+        Host: 239.255.255.250:1900\r\n\
+        s: uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a\r\n\
+        Man: ssdp:discover\r\n\r\n";
+
+    let (s, st, mx) = parse_ssdp_discover(discover.as_bytes()).unwrap();
+
+    assert_eq!(Some("uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a".to_string()), s);
+    assert_eq!("my:service", st);
+    assert_eq!(Some(3), mx);
+}
+
+#[test]
+fn test_parse_discover_wrong_man() {
+    let discover = "M-SEARCH * HTTP/1.1\r\nS: uuid:foo\r\nHost: 239.255.255.250:1900\r\nMAN: \"ssdp:alive\"\r\nST: my:service\r\nMX: 1\r\n\r\n";
+
+    assert!(parse_ssdp_discover(discover.as_bytes()).is_none());
+}
+
+#[test]
+fn test_parse_discover_not_msearch() {
+    let alive = generate_ssdp_alive(
+        &ServiceDescription {
+            usn_uri: "uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a".to_string(),
+            service_type_uri: "test:application".to_string(),
+            expiration: 42,
+            location: "https://foo/bar".to_string(),
+        },
+        &MulticastAddr::V4,
+        None,
+    );
+
+    assert!(parse_ssdp_discover(alive.as_bytes()).is_none());
+}
+
+#[test]
+fn test_parse_max_age() {
+    assert_eq!(Some(42), parse_max_age("max-age=42"));
+    assert_eq!(Some(42), parse_max_age("max-age = 42"));
+    assert_eq!(Some(42), parse_max_age("MAX-AGE=42"));
+    assert_eq!(Some(42), parse_max_age("max-age=\"42\""));
+    assert_eq!(Some(42), parse_max_age("no-cache=\"Ext\", max-age=42"));
+    assert_eq!(Some(42), parse_max_age("no-cache=\"Ext, Foo\",max-age = 42"));
+    assert_eq!(Some(42), parse_max_age("public, max-age=42, must-revalidate"));
+    assert_eq!(None, parse_max_age("no-cache=\"max-age=42\""));
+    assert_eq!(None, parse_max_age("no-cache"));
+    assert_eq!(None, parse_max_age("max-age=forever"));
+    assert_eq!(None, parse_max_age(""));
+}
+
+#[test]
+fn test_parse_discover_answer_location() {
+    // UPnP devices send LOCATION instead of AL
+    let answer = "HTTP/1.1 200 OK\r\n\
+        CACHE-CONTROL: max-age=100\r\n\
+        EXT:\r\n\
+        LOCATION: http://192.168.1.2:80/description.xml\r\n\
+        SERVER: Linux/3.14.0 UPnP/1.0 IpBridge/1.26.0\r\n\
+        ST: upnp:rootdevice\r\n\
+        USN: uuid:2f402f80-da50-11e1-9b23-001788255acc::upnp:rootdevice\r\n\r\n";
+
+    let parsed = parse_answer(answer.as_bytes()).unwrap();
+
+    assert_eq!("http://192.168.1.2:80/description.xml", parsed.description.location);
+    assert_eq!(vec!["http://192.168.1.2:80/description.xml".to_string()], parsed.locations);
+    assert_eq!(Some("Linux/3.14.0 UPnP/1.0 IpBridge/1.26.0".to_string()), parsed.server);
+}
+
+#[test]
+fn test_parse_al() {
+    assert_eq!(
+        vec!["my:service".to_string(), "https://foo/bar".to_string()],
+        parse_al("<my:service><https://foo/bar>")
+    );
+    assert_eq!(
+        vec!["my:service".to_string(), "https://foo/bar".to_string(), "http://10.0.0.1/bar".to_string()],
+        parse_al(" <my:service> < https://foo/bar ><http://10.0.0.1/bar>")
+    );
+    assert_eq!(vec!["https://foo/bar".to_string()], parse_al("https://foo/bar"));
+    assert!(parse_al("").is_empty());
+    assert!(parse_al("<>").is_empty());
+}
+
+#[test]
+fn test_locations() {
+    let al = parse_al("<my:service><https://foo/bar><http://10.0.0.1/bar>");
+
+    assert_eq!(
+        vec!["https://foo/bar".to_string(), "http://10.0.0.1/bar".to_string()],
+        locations(&al, None)
+    );
+    assert_eq!(
+        vec!["http://10.0.0.1/bar".to_string(), "https://foo/bar".to_string()],
+        locations(&al, Some("http://10.0.0.1/bar"))
+    );
+    assert_eq!(
+        vec!["uuid:foo".to_string()],
+        locations(&parse_al("<my:service><uuid:foo>"), None)
+    );
+    assert!(locations(&[], None).is_empty());
+}
+
+#[test]
+fn test_upnp_alive() {
+    let alive = generate_ssdp_alive(
+        &ServiceDescription {
+            usn_uri: "uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a::urn:schemas-upnp-org:device:Basic:1".to_string(),
+            service_type_uri: "urn:schemas-upnp-org:device:Basic:1".to_string(),
+            expiration: 1800,
+            location: "http://10.0.0.2:8080/description.xml".to_string(),
+        },
+        &MulticastAddr::V4,
+        Some(&UpnpHeaders {
+            server: "Linux/6.1 UPnP/2.0 test/1.0",
+            boot_id: 7,
+            config_id: 3,
+            search_port: None,
+        }),
+    );
+
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut req = httparse::Request::new(&mut headers);
+    req.parse(alive.as_bytes()).unwrap();
+    let headers = Headers::new(req.headers);
+
+    assert_eq!(None, headers.get("AL"));
+    assert_eq!(Some("http://10.0.0.2:8080/description.xml"), headers.get("LOCATION"));
+    assert_eq!(Some("Linux/6.1 UPnP/2.0 test/1.0"), headers.get("SERVER"));
+    assert_eq!(Some("7"), headers.get("BOOTID.UPNP.ORG"));
+    assert_eq!(Some("3"), headers.get("CONFIGID.UPNP.ORG"));
+    assert_eq!(Some("max-age = 1800"), headers.get("CACHE-CONTROL"));
+}
+
+#[test]
+fn test_upnp_byebye() {
+    let byebye = generate_ssdp_byebye(
+        &ServiceDescription {
+            usn_uri: "uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a".to_string(),
+            service_type_uri: "upnp:rootdevice".to_string(),
+            expiration: 1800,
+            location: "http://10.0.0.2:8080/description.xml".to_string(),
+        },
+        &MulticastAddr::V4,
+        Some(&UpnpHeaders {
+            server: "Linux/6.1 UPnP/2.0 test/1.0",
+            boot_id: 7,
+            config_id: 3,
+            search_port: None,
+        }),
+    );
+
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut req = httparse::Request::new(&mut headers);
+    req.parse(byebye.as_bytes()).unwrap();
+    let headers = Headers::new(req.headers);
+
+    assert_eq!(Some("ssdp:byebye"), headers.get("NTS"));
+    assert_eq!(Some("7"), headers.get("BOOTID.UPNP.ORG"));
+    assert_eq!(Some("3"), headers.get("CONFIGID.UPNP.ORG"));
+}
+
+#[test]
+fn test_upnp_discover_answer_roundtrip() {
+    let desc = ServiceDescription {
+        usn_uri: "uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a::upnp:rootdevice".to_string(),
+        service_type_uri: "upnp:rootdevice".to_string(),
+        expiration: 1800,
+        location: "http://10.0.0.2:8080/description.xml".to_string(),
+    };
+    let answer = generate_ssdp_discover_answer(
+        &desc,
+        None,
+        Some(&UpnpHeaders {
+            server: "Linux/6.1 UPnP/2.0 test/1.0",
+            boot_id: 7,
+            config_id: 3,
+            search_port: None,
+        }),
+    );
+
+    assert!(!answer.contains("AL:"));
+    assert!(!answer.contains("S:"));
+
+    let parsed = parse_answer(answer.as_bytes()).unwrap();
+
+    assert_eq!(desc, parsed.description);
+    assert_eq!(Some("Linux/6.1 UPnP/2.0 test/1.0".to_string()), parsed.server);
+    assert_eq!(Some(7), parsed.boot_id);
+    assert_eq!(Some(3), parsed.config_id);
+    assert_eq!(None, parsed.search_port);
+}
+
+#[test]
+fn test_upnp_search_port() {
+    let desc = ServiceDescription {
+        usn_uri: "uuid:some-service-uuid".to_string(),
+        service_type_uri: "some:special:service".to_string(),
+        expiration: 1800,
+        location: "http://10.0.0.2:8080/description.xml".to_string(),
+    };
+    let upnp = UpnpHeaders {
+        server: "Linux/6.1 UPnP/2.0 test/1.0",
+        boot_id: 7,
+        config_id: 3,
+        search_port: Some(41900),
+    };
+
+    let alive = generate_ssdp_alive(&desc, &MulticastAddr::V4, Some(&upnp));
+    assert!(alive.contains("SEARCHPORT.UPNP.ORG: 41900\r\n"));
+
+    let answer = generate_ssdp_discover_answer(&desc, None, Some(&upnp));
+    assert_eq!(Some(41900), parse_answer(answer.as_bytes()).unwrap().search_port);
+}
+
+#[test]
+fn test_upnp_discover() {
+    let discover = generate_ssdp_discover(
+        None,
+        "ssdp:all".to_string(),
+        &MulticastAddr::V4,
+        3,
+        Some("Linux/6.1 UPnP/2.0 test/1.0".to_string()),
+    );
+
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut req = httparse::Request::new(&mut headers);
+    req.parse(discover.as_bytes()).unwrap();
+    let headers = Headers::new(req.headers);
+
+    assert_eq!(None, headers.get("S"));
+    assert_eq!(Some("Linux/6.1 UPnP/2.0 test/1.0"), headers.get("USER-AGENT"));
+    assert_eq!(Some("\"ssdp:discover\""), headers.get("MAN"));
+
+    let (s, st, mx) = parse_ssdp_discover(discover.as_bytes()).unwrap();
+
+    assert_eq!(None, s);
+    assert_eq!("ssdp:all", st);
+    assert_eq!(Some(3), mx);
+}
+
+#[test]
+fn test_discover_v6_host() {
+    let discover = generate_ssdp_discover(
+        Some("uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a".to_string()),
+        "my:service".to_string(),
+        &MulticastAddr::V6LinkLocal,
+        1,
+        None,
+    );
+
+    assert!(discover.contains("Host: [ff02::c]:1900\r\n"));
+
+    let byebye = generate_ssdp_byebye(
+        &ServiceDescription {
+            usn_uri: "uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a".to_string(),
+            service_type_uri: "test:application".to_string(),
+            expiration: 42,
+            location: "https://foo/bar".to_string(),
+        },
+        &MulticastAddr::V6SiteLocal,
+        None,
+    );
+
+    assert!(byebye.contains("Host: [ff05::c]:1900\r\n"));
+}
+
+#[test]
+fn test_discover_custom_host() {
+    let discover = generate_ssdp_discover(
+        None,
+        "my:service".to_string(),
+        &MulticastAddr::Custom("239.255.77.77:41900".parse().unwrap()),
+        1,
+        None,
+    );
+
+    assert!(discover.contains("Host: 239.255.77.77:41900\r\n"));
+
+    let discover = generate_ssdp_discover(
+        None,
+        "my:service".to_string(),
+        &MulticastAddr::Custom("[ff15::77]:41900".parse().unwrap()),
+        1,
+        None,
+    );
+
+    assert!(discover.contains("Host: [ff15::77]:41900\r\n"));
+}
@@ -0,0 +1,98 @@
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+
+use if_addrs::IfAddr;
+
+use crate::Error;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// A network interface to join the multicast group on and to send multicasts from
+pub enum Interface {
+    /// The name of the interface
+    ///
+    /// ```text
+    /// eth0
+    /// ```
+    Name(String),
+
+    /// The index of the interface, as used for the IPv6 scope id
+    Index(u32),
+
+    /// Any address assigned to the interface
+    Addr(IpAddr),
+}
+
+/// A network interface found on this host
+#[derive(Clone, Debug)]
+pub(crate) struct NetworkInterface {
+    pub(crate) name: String,
+    pub(crate) index: u32,
+    addrs: Vec<IfAddr>,
+}
+
+impl NetworkInterface {
+    /// The first IPv4 address of this interface
+    pub(crate) fn v4(&self) -> Option<Ipv4Addr> {
+        self.addrs.iter().find_map(|addr| match addr {
+            IfAddr::V4(addr) => Some(addr.ip),
+            IfAddr::V6(_) => None,
+        })
+    }
+
+    /// Returns `true` if `ip` is within one of the networks of this interface
+    pub(crate) fn contains(&self, ip: IpAddr) -> bool {
+        self.addrs.iter().any(|addr| match (addr, ip) {
+            (IfAddr::V4(addr), IpAddr::V4(ip)) => {
+                let netmask = u32::from(addr.netmask);
+                u32::from(addr.ip) & netmask == u32::from(ip) & netmask
+            }
+            (IfAddr::V6(addr), IpAddr::V6(ip)) => {
+                let netmask = u128::from(addr.netmask);
+                u128::from(addr.ip) & netmask == u128::from(ip) & netmask
+            }
+            _ => false,
+        })
+    }
+
+    fn matches(&self, interface: &Interface) -> bool {
+        match interface {
+            Interface::Name(name) => &self.name == name,
+            Interface::Index(index) => self.index == *index,
+            Interface::Addr(ip) => self.addrs.iter().any(|addr| addr.ip() == *ip),
+        }
+    }
+}
+
+/// Looks up the given interfaces on this host
+///
+/// Fails if one of them does not exist.
+pub(crate) fn resolve_interfaces(interfaces: &[Interface]) -> Result<Vec<NetworkInterface>, Error> {
+    if interfaces.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut available: Vec<NetworkInterface> = vec![];
+    let found_interfaces =
+        if_addrs::get_if_addrs().map_err(|e| Error::Interface(e.to_string()))?;
+    for found in found_interfaces {
+        match available.iter_mut().find(|available| available.name == found.name) {
+            Some(available) => available.addrs.push(found.addr),
+            None => available.push(NetworkInterface {
+                name: found.name,
+                index: found.index.unwrap_or_default(),
+                addrs: vec![found.addr],
+            }),
+        }
+    }
+
+    interfaces
+        .iter()
+        .map(|interface| {
+            available
+                .iter()
+                .find(|available| available.matches(interface))
+                .cloned()
+                .ok_or_else(|| Error::Interface(format!("{:?} not found", interface)))
+        })
+        .collect()
+}
@@ -2,19 +2,54 @@ use std::convert::Into;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::Ipv6Addr;
+use std::net::SocketAddr;
 
 pub mod client;
+pub mod device;
+mod error;
 mod http_helper;
+pub mod interface;
+pub mod message;
 pub mod service;
 
+#[cfg(test)]
+mod client_test;
+#[cfg(test)]
+mod device_test;
 #[cfg(test)]
 mod http_helper_test;
+#[cfg(test)]
+mod message_test;
+#[cfg(test)]
+mod service_test;
 mod socket_helper;
+#[cfg(test)]
+mod socket_helper_test;
+
+pub use error::Error;
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+/// The flavour of SSDP spoken by a [client::Client] or [service::Service]
+///
+/// Both flavours are understood when receiving, this selects what is sent.
+pub enum Protocol {
+    /// The [RFC draft](https://datatracker.ietf.org/doc/html/draft-cai-ssdp-v1-03)
+    ///
+    /// Sends the `S` and `AL` headers
+    #[default]
+    Draft,
+
+    /// The [UPnP Device Architecture](https://openconnectivity.org/upnp-specs/UPnP-arch-DeviceArchitecture-v2.0-20200417.pdf)
+    ///
+    /// Sends the `LOCATION`, `SERVER`, `EXT`, `BOOTID.UPNP.ORG`, `CONFIGID.UPNP.ORG` and `USER-AGENT` headers,
+    /// so off-the-shelf UPnP devices and control points understand us
+    Upnp,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 /// The Multicast Address in use
 ///
-/// This uses officially assigned addresses by IANA
+/// This uses officially assigned addresses by IANA, unless a [MulticastAddr::Custom] one is given
 pub enum MulticastAddr {
     /// The IPv4 Multicast address: `239.255.255.250`
     ///
@@ -31,10 +66,19 @@ pub enum MulticastAddr {
     /// This is referred to as `site-local`
     V6SiteLocal,
 
-    /// This is used only for test purposes or a local setup
-    /// 
-    /// Multicast is not redirected to the originating host, so we cannot run a Service and a Client on the same device
+    /// The IPv4 Multicast address `239.255.255.250` on the loopback interface
+    ///
+    /// This is used only for test purposes or a local setup. Multicasts never leave this host but are looped back to it,
+    /// so Services and Clients on the same host see each other, as long as multicast loop stays enabled.
     Loopback,
+
+    /// Any group and port, e.g. a private discovery group: `239.255.77.77:41900`
+    ///
+    /// Keeps clear of the SSDP daemon of the system. A unicast address is sent to directly, without joining a group,
+    /// e.g. `[::1]:41900` to test IPv6 locally, as IPv6 multicast is commonly not routed on the loopback interface.
+    /// Only a single Service listening there gets each unicast, so it answers searches, but `NOTIFY` sent there
+    /// reach just one Client.
+    Custom(SocketAddr),
 }
 
 impl MulticastAddr {
@@ -45,6 +89,30 @@ impl MulticastAddr {
             MulticastAddr::V6LinkLocal => self.get_v6().unwrap().into(),
             MulticastAddr::V6SiteLocal => self.get_v6().unwrap().into(),
             MulticastAddr::Loopback => self.get_v4().unwrap().into(),
+            MulticastAddr::Custom(addr) => addr.ip(),
+        }
+    }
+
+    /// Returns the port SSDP messages are sent to, `1900` unless a [MulticastAddr::Custom] one is given
+    pub fn port(&self) -> u16 {
+        match self {
+            MulticastAddr::Custom(addr) => addr.port(),
+            _ => SSDP_PORT,
+        }
+    }
+
+    /// Returns the address SSDP messages are sent to, as written into the `Host` header
+    ///
+    /// IPv6 addresses are bracketed: `[ff02::c]:1900`
+    pub(crate) fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.get_ip(), self.port())
+    }
+
+    /// Returns the IPv6 scope id of a [MulticastAddr::Custom] one like `[ff02::c%3]:41900`, `0` otherwise
+    pub(crate) fn scope_id(&self) -> u32 {
+        match self {
+            MulticastAddr::Custom(SocketAddr::V6(addr)) => addr.scope_id(),
+            _ => 0,
         }
     }
 
@@ -55,6 +123,7 @@ impl MulticastAddr {
             MulticastAddr::V6LinkLocal => false,
             MulticastAddr::V6SiteLocal => false,
             MulticastAddr::Loopback => true,
+            MulticastAddr::Custom(addr) => addr.is_ipv4(),
         }
     }
 
@@ -64,7 +133,9 @@ impl MulticastAddr {
             MulticastAddr::V4 => Some(Ipv4Addr::new(239, 255, 255, 250)),
             MulticastAddr::V6LinkLocal => None,
             MulticastAddr::V6SiteLocal => None,
-            MulticastAddr::Loopback => Some(Ipv4Addr::new(127, 0, 0, 1)),
+            MulticastAddr::Loopback => Some(Ipv4Addr::new(239, 255, 255, 250)),
+            MulticastAddr::Custom(SocketAddr::V4(addr)) => Some(*addr.ip()),
+            MulticastAddr::Custom(SocketAddr::V6(_)) => None,
         }
     }
 
@@ -75,6 +146,8 @@ impl MulticastAddr {
             MulticastAddr::V6LinkLocal => Some(Ipv6Addr::new(0xFF02, 0, 0, 0, 0, 0, 0, 0xC)),
             MulticastAddr::V6SiteLocal => Some(Ipv6Addr::new(0xFF05, 0, 0, 0, 0, 0, 0, 0xC)),
             MulticastAddr::Loopback => None,
+            MulticastAddr::Custom(SocketAddr::V4(_)) => None,
+            MulticastAddr::Custom(SocketAddr::V6(addr)) => Some(*addr.ip()),
         }
     }
 }
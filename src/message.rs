@@ -0,0 +1,464 @@
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+use log::trace;
+
+use crate::http_helper::parse_al;
+use crate::http_helper::parse_max_age;
+use crate::http_helper::Headers;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// A single SSDP message as it is sent over the wire
+///
+/// Use [SsdpMessage::parse] to read a message from a received datagram and [SsdpMessage::to_bytes]
+/// (or [Display]) to render it.
+/// Rendering and parsing are inverse to each other, so `SsdpMessage::parse(&m.to_bytes()) == Ok(m)`
+/// for every message carrying a location in `AL` or `LOCATION`.
+///
+/// Headers of the [draft](https://datatracker.ietf.org/doc/html/draft-cai-ssdp-v1-03) and of
+/// [UPnP](https://openconnectivity.org/upnp-specs/UPnP-arch-DeviceArchitecture-v2.0-20200417.pdf) are both understood,
+/// optional headers are only rendered if they are set.
+pub enum SsdpMessage {
+    /// `M-SEARCH` request multicasted by a [crate::client::Client]
+    MSearch {
+        /// `S` - The unique identifier of the requesting [crate::client::Client]
+        s: Option<String>,
+        /// `Host` - The multicast address and port the search is sent to
+        host: String,
+        /// `ST` - The search target, e.g. `ssdp:all` or `my:service`
+        st: String,
+        /// `MX` - The maximum time in seconds a [crate::service::Service] may wait before answering
+        mx: Option<u32>,
+        /// `USER-AGENT` - UPnP: The OS and product of the [crate::client::Client]
+        user_agent: Option<String>,
+    },
+
+    /// `200 OK` answer of a [crate::service::Service] to a `M-SEARCH` request
+    SearchResponse {
+        /// `S` - The unique identifier of the requesting [crate::client::Client], copied from the request
+        s: Option<String>,
+        /// `Cache-Control` - Sent as `no-cache="Ext", max-age=100`, see [SsdpMessage::max_age]
+        cache_control: Option<String>,
+        /// `ST` - The search target this answer matches
+        st: String,
+        /// `USN` - The unique service name
+        usn: String,
+        /// `AL` - The bracketed list of locations, e.g. `<my:service><https://foo/bar>`
+        al: Vec<String>,
+        /// `LOCATION` - The UPnP location of the service, used instead of or next to `AL`
+        location: Option<String>,
+        /// `SERVER` - UPnP: The OS and product of the [crate::service::Service]
+        server: Option<String>,
+        /// `BOOTID.UPNP.ORG` - UPnP: Increased every time the device comes up
+        boot_id: Option<u32>,
+        /// `CONFIGID.UPNP.ORG` - UPnP: Changes whenever the device description changes
+        config_id: Option<u32>,
+        /// `SEARCHPORT.UPNP.ORG` - UPnP: The port to send unicast `M-SEARCH` to, if not the SSDP port
+        search_port: Option<u16>,
+    },
+
+    /// `NOTIFY` with `NTS: ssdp:alive`, multicasted when a [crate::service::Service] comes up
+    NotifyAlive {
+        /// `Host` - The multicast address and port the notification is sent to
+        host: String,
+        /// `NT` - The notification type, usually the service type
+        nt: String,
+        /// `USN` - The unique service name
+        usn: String,
+        /// `AL` - The bracketed list of locations, e.g. `<uuid:...><https://foo/bar>`
+        al: Vec<String>,
+        /// `LOCATION` - The UPnP location of the service, used instead of or next to `AL`
+        location: Option<String>,
+        /// `Cache-Control` - Sent as `max-age = 100`, see [SsdpMessage::max_age]
+        cache_control: Option<String>,
+        /// `SERVER` - UPnP: The OS and product of the [crate::service::Service]
+        server: Option<String>,
+        /// `BOOTID.UPNP.ORG` - UPnP: Increased every time the device comes up
+        boot_id: Option<u32>,
+        /// `CONFIGID.UPNP.ORG` - UPnP: Changes whenever the device description changes
+        config_id: Option<u32>,
+        /// `SEARCHPORT.UPNP.ORG` - UPnP: The port to send unicast `M-SEARCH` to, if not the SSDP port
+        search_port: Option<u16>,
+    },
+
+    /// `NOTIFY` with `NTS: ssdp:byebye`, multicasted when a [crate::service::Service] goes down
+    NotifyByebye {
+        /// `Host` - The multicast address and port the notification is sent to
+        host: String,
+        /// `NT` - The notification type, usually the service type
+        nt: String,
+        /// `USN` - The unique service name
+        usn: String,
+        /// `BOOTID.UPNP.ORG` - UPnP: Increased every time the device comes up
+        boot_id: Option<u32>,
+        /// `CONFIGID.UPNP.ORG` - UPnP: Changes whenever the device description changes
+        config_id: Option<u32>,
+    },
+
+    /// `NOTIFY` with `NTS: ssdp:update`, multicasted when a [crate::service::Service] changes
+    NotifyUpdate {
+        /// `Host` - The multicast address and port the notification is sent to
+        host: String,
+        /// `NT` - The notification type, usually the service type
+        nt: String,
+        /// `USN` - The unique service name
+        usn: String,
+        /// `AL` - The bracketed list of locations, e.g. `<uuid:...><https://foo/bar>`
+        al: Vec<String>,
+        /// `LOCATION` - The UPnP location of the service, used instead of or next to `AL`
+        location: Option<String>,
+        /// `BOOTID.UPNP.ORG` - UPnP: The boot id used so far
+        boot_id: Option<u32>,
+        /// `CONFIGID.UPNP.ORG` - UPnP: Changes whenever the device description changes
+        config_id: Option<u32>,
+        /// `NEXTBOOTID.UPNP.ORG` - UPnP: The boot id used from now on
+        next_boot_id: Option<u32>,
+        /// `SEARCHPORT.UPNP.ORG` - UPnP: The port to send unicast `M-SEARCH` to, if not the SSDP port
+        search_port: Option<u16>,
+    },
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// Reasons why a datagram is not a valid [SsdpMessage]
+pub enum ParseError {
+    /// The datagram is not a complete HTTP message
+    Http(String),
+
+    /// The request method is neither `M-SEARCH` nor `NOTIFY`
+    UnknownMethod(String),
+
+    /// The response status is not `200`
+    UnexpectedStatus(u16),
+
+    /// A mandatory header is not present
+    MissingHeader(&'static str),
+
+    /// A header is present but its value is not understood
+    InvalidHeader(&'static str, String),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Http(e) => write!(f, "not a valid HTTP message: {}", e),
+            ParseError::UnknownMethod(method) => write!(f, "unknown method {}", method),
+            ParseError::UnexpectedStatus(code) => write!(f, "unexpected status code {}", code),
+            ParseError::MissingHeader(name) => write!(f, "{} header is not present", name),
+            ParseError::InvalidHeader(name, value) => {
+                write!(f, "{} header has an invalid value {:?}", name, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl SsdpMessage {
+    /// Parses a received datagram
+    ///
+    /// Headers may be sent in any order and spelling.
+    pub fn parse(buf: &[u8]) -> Result<Self, ParseError> {
+        if buf.starts_with(b"HTTP/") {
+            Self::parse_response(buf)
+        } else {
+            Self::parse_request(buf)
+        }
+    }
+
+    /// The `max-age` of the `Cache-Control` header in seconds, if the message has one
+    pub fn max_age(&self) -> Option<u32> {
+        match self {
+            SsdpMessage::SearchResponse { cache_control, .. } | SsdpMessage::NotifyAlive { cache_control, .. } => {
+                cache_control.as_deref().and_then(parse_max_age)
+            }
+            _ => None,
+        }
+    }
+
+    /// Renders the message to the bytes sent over the wire
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+
+    fn parse_response(buf: &[u8]) -> Result<Self, ParseError> {
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut resp = httparse::Response::new(&mut headers);
+
+        let status = httparse::ParserConfig::default()
+            .allow_spaces_after_header_name_in_responses(true)
+            .parse_response(&mut resp, buf)
+            .map_err(|e| ParseError::Http(e.to_string()))?;
+        if status.is_partial() {
+            return Err(ParseError::Http("incomplete message".to_string()));
+        }
+
+        let code = resp.code.expect("Complete responses have a status code");
+        if code != 200 {
+            return Err(ParseError::UnexpectedStatus(code));
+        }
+
+        let headers = Headers::new(resp.headers);
+
+        Ok(SsdpMessage::SearchResponse {
+            s: optional(&headers, "S"),
+            cache_control: optional(&headers, "Cache-Control"),
+            st: required(&headers, "ST")?,
+            usn: required(&headers, "USN")?,
+            al: al(&headers)?,
+            location: optional(&headers, "LOCATION"),
+            server: optional(&headers, "SERVER"),
+            boot_id: upnp_numeric(&headers, "BOOTID.UPNP.ORG"),
+            config_id: upnp_numeric(&headers, "CONFIGID.UPNP.ORG"),
+            search_port: upnp_numeric(&headers, "SEARCHPORT.UPNP.ORG"),
+        })
+    }
+
+    fn parse_request(buf: &[u8]) -> Result<Self, ParseError> {
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut req = httparse::Request::new(&mut headers);
+
+        let status = req
+            .parse(buf)
+            .map_err(|e| ParseError::Http(e.to_string()))?;
+        if status.is_partial() {
+            return Err(ParseError::Http("incomplete message".to_string()));
+        }
+
+        let method = req.method.expect("Complete requests have a method");
+        let headers = Headers::new(req.headers);
+
+        match method {
+            "M-SEARCH" => {
+                // TODO error in RFC? MAN is the only quoted parameter, so accept it either way
+                let man = required(&headers, "MAN")?;
+                if man.trim_matches('"') != "ssdp:discover" {
+                    return Err(ParseError::InvalidHeader("MAN", man));
+                }
+
+                Ok(SsdpMessage::MSearch {
+                    s: optional(&headers, "S"),
+                    host: required(&headers, "Host")?,
+                    st: required(&headers, "ST")?,
+                    mx: numeric(&headers, "MX")?,
+                    user_agent: optional(&headers, "USER-AGENT"),
+                })
+            }
+            "NOTIFY" => {
+                let nts = required(&headers, "NTS")?;
+                let host = required(&headers, "Host")?;
+                let nt = required(&headers, "NT")?;
+                let usn = required(&headers, "USN")?;
+                let boot_id = upnp_numeric(&headers, "BOOTID.UPNP.ORG");
+                let config_id = upnp_numeric(&headers, "CONFIGID.UPNP.ORG");
+
+                match nts.as_str() {
+                    "ssdp:alive" => Ok(SsdpMessage::NotifyAlive {
+                        host,
+                        nt,
+                        usn,
+                        al: al(&headers)?,
+                        location: optional(&headers, "LOCATION"),
+                        cache_control: optional(&headers, "Cache-Control"),
+                        server: optional(&headers, "SERVER"),
+                        boot_id,
+                        config_id,
+                        search_port: upnp_numeric(&headers, "SEARCHPORT.UPNP.ORG"),
+                    }),
+                    "ssdp:byebye" => Ok(SsdpMessage::NotifyByebye {
+                        host,
+                        nt,
+                        usn,
+                        boot_id,
+                        config_id,
+                    }),
+                    "ssdp:update" => Ok(SsdpMessage::NotifyUpdate {
+                        host,
+                        nt,
+                        usn,
+                        al: al(&headers)?,
+                        location: optional(&headers, "LOCATION"),
+                        boot_id,
+                        config_id,
+                        next_boot_id: upnp_numeric(&headers, "NEXTBOOTID.UPNP.ORG"),
+                        search_port: upnp_numeric(&headers, "SEARCHPORT.UPNP.ORG"),
+                    }),
+                    _ => Err(ParseError::InvalidHeader("NTS", nts)),
+                }
+            }
+            method => Err(ParseError::UnknownMethod(method.to_string())),
+        }
+    }
+}
+
+fn optional(headers: &Headers, name: &'static str) -> Option<String> {
+    headers.get(name).map(|value| value.to_string())
+}
+
+fn required(headers: &Headers, name: &'static str) -> Result<String, ParseError> {
+    optional(headers, name).ok_or(ParseError::MissingHeader(name))
+}
+
+/// Reads an optional header holding a number
+fn numeric<T: FromStr>(headers: &Headers, name: &'static str) -> Result<Option<T>, ParseError> {
+    match headers.get(name) {
+        None => Ok(None),
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| ParseError::InvalidHeader(name, value.to_string())),
+    }
+}
+
+/// Reads an optional UPnP header holding a number, like `BOOTID.UPNP.ORG`
+///
+/// Devices get these wrong every so often, so an invalid value is dropped instead of the whole message.
+fn upnp_numeric<T: FromStr>(headers: &Headers, name: &'static str) -> Option<T> {
+    let value = headers.get(name)?;
+    let parsed = value.parse().ok();
+    if parsed.is_none() {
+        trace!("Ignoring invalid {} header: {}", name, value);
+    }
+
+    parsed
+}
+
+/// Reads the `AL` header, which may only be left out if there is a `LOCATION` header
+fn al(headers: &Headers) -> Result<Vec<String>, ParseError> {
+    match headers.get("AL") {
+        Some(al) => Ok(parse_al(al)),
+        None if headers.get("LOCATION").is_some() => Ok(vec![]),
+        None => Err(ParseError::MissingHeader("AL")),
+    }
+}
+
+/// Writes a header if it has a value
+fn write_optional(f: &mut Formatter<'_>, name: &str, value: &Option<impl Display>) -> fmt::Result {
+    match value {
+        Some(value) => write!(f, "{}: {}\r\n", name, value),
+        None => Ok(()),
+    }
+}
+
+/// Writes the `AL` header unless there are no locations
+fn write_al(f: &mut Formatter<'_>, al: &[String]) -> fmt::Result {
+    if al.is_empty() {
+        return Ok(());
+    }
+
+    write!(f, "AL: ")?;
+    for uri in al {
+        write!(f, "<{}>", uri)?;
+    }
+    write!(f, "\r\n")
+}
+
+impl Display for SsdpMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SsdpMessage::MSearch {
+                s,
+                host,
+                st,
+                mx,
+                user_agent,
+            } => {
+                write!(f, "M-SEARCH * HTTP/1.1\r\n")?;
+                write_optional(f, "S", s)?;
+                write!(f, "Host: {}\r\nMAN: \"ssdp:discover\"\r\nST: {}\r\n", host, st)?;
+                write_optional(f, "MX", mx)?;
+                write_optional(f, "USER-AGENT", user_agent)?;
+            }
+            SsdpMessage::SearchResponse {
+                s,
+                cache_control,
+                st,
+                usn,
+                al,
+                location,
+                server,
+                boot_id,
+                config_id,
+                search_port,
+            } => {
+                write!(f, "HTTP/1.1 200 OK\r\n")?;
+                write_optional(f, "S", s)?;
+                write!(f, "Ext: \r\n")?;
+                write_optional(f, "Cache-Control", cache_control)?;
+                write!(f, "ST: {}\r\nUSN: {}\r\n", st, usn)?;
+                write_al(f, al)?;
+                write_optional(f, "LOCATION", location)?;
+                write_optional(f, "SERVER", server)?;
+                write_optional(f, "BOOTID.UPNP.ORG", boot_id)?;
+                write_optional(f, "CONFIGID.UPNP.ORG", config_id)?;
+                write_optional(f, "SEARCHPORT.UPNP.ORG", search_port)?;
+            }
+            SsdpMessage::NotifyAlive {
+                host,
+                nt,
+                usn,
+                al,
+                location,
+                cache_control,
+                server,
+                boot_id,
+                config_id,
+                search_port,
+            } => {
+                write!(
+                    f,
+                    "NOTIFY * HTTP/1.1\r\nHost: {}\r\nNT: {}\r\nNTS: ssdp:alive\r\nUSN: {}\r\n",
+                    host, nt, usn
+                )?;
+                write_al(f, al)?;
+                write_optional(f, "Cache-Control", cache_control)?;
+                write_optional(f, "LOCATION", location)?;
+                write_optional(f, "SERVER", server)?;
+                write_optional(f, "BOOTID.UPNP.ORG", boot_id)?;
+                write_optional(f, "CONFIGID.UPNP.ORG", config_id)?;
+                write_optional(f, "SEARCHPORT.UPNP.ORG", search_port)?;
+            }
+            SsdpMessage::NotifyByebye {
+                host,
+                nt,
+                usn,
+                boot_id,
+                config_id,
+            } => {
+                write!(
+                    f,
+                    "NOTIFY * HTTP/1.1\r\nHost: {}\r\nNT: {}\r\nNTS: ssdp:byebye\r\nUSN: {}\r\n",
+                    host, nt, usn
+                )?;
+                write_optional(f, "BOOTID.UPNP.ORG", boot_id)?;
+                write_optional(f, "CONFIGID.UPNP.ORG", config_id)?;
+            }
+            SsdpMessage::NotifyUpdate {
+                host,
+                nt,
+                usn,
+                al,
+                location,
+                boot_id,
+                config_id,
+                next_boot_id,
+                search_port,
+            } => {
+                write!(
+                    f,
+                    "NOTIFY * HTTP/1.1\r\nHost: {}\r\nNT: {}\r\nNTS: ssdp:update\r\nUSN: {}\r\n",
+                    host, nt, usn
+                )?;
+                write_al(f, al)?;
+                write_optional(f, "LOCATION", location)?;
+                write_optional(f, "BOOTID.UPNP.ORG", boot_id)?;
+                write_optional(f, "CONFIGID.UPNP.ORG", config_id)?;
+                write_optional(f, "NEXTBOOTID.UPNP.ORG", next_boot_id)?;
+                write_optional(f, "SEARCHPORT.UPNP.ORG", search_port)?;
+            }
+        }
+
+        write!(f, "\r\n")
+    }
+}
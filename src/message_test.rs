@@ -0,0 +1,316 @@
+use proptest::prelude::*;
+
+use crate::message::ParseError;
+use crate::message::SsdpMessage;
+
+/// A header value as it survives the wire: printable and without surrounding whitespace
+fn header_value() -> impl Strategy<Value = String> {
+    "[!-~]([ -~]{0,30}[!-~])?"
+}
+
+/// A URI within the `AL` header, which can't contain the brackets
+fn al_uri() -> impl Strategy<Value = String> {
+    "[!-;=?-~]{1,30}"
+}
+
+fn al() -> impl Strategy<Value = Vec<String>> {
+    proptest::collection::vec(al_uri(), 0..4)
+}
+
+/// `AL` and `LOCATION`, at least one of them set
+fn locations() -> impl Strategy<Value = (Vec<String>, Option<String>)> {
+    (al(), proptest::option::of(header_value()))
+        .prop_filter("AL or LOCATION required", |(al, location)| {
+            !al.is_empty() || location.is_some()
+        })
+}
+
+/// `SERVER`, `BOOTID.UPNP.ORG`, `CONFIGID.UPNP.ORG` and `SEARCHPORT.UPNP.ORG`
+fn upnp_headers() -> impl Strategy<Value = (Option<String>, Option<u32>, Option<u32>, Option<u16>)> {
+    (
+        proptest::option::of(header_value()),
+        proptest::option::of(any::<u32>()),
+        proptest::option::of(any::<u32>()),
+        proptest::option::of(any::<u16>()),
+    )
+}
+
+fn ssdp_message() -> impl Strategy<Value = SsdpMessage> {
+    prop_oneof![
+        (
+            proptest::option::of(header_value()),
+            header_value(),
+            header_value(),
+            proptest::option::of(any::<u32>()),
+            proptest::option::of(header_value()),
+        )
+            .prop_map(|(s, host, st, mx, user_agent)| SsdpMessage::MSearch {
+                s,
+                host,
+                st,
+                mx,
+                user_agent,
+            }),
+        (
+            proptest::option::of(header_value()),
+            proptest::option::of(header_value()),
+            header_value(),
+            header_value(),
+            locations(),
+            upnp_headers(),
+        )
+            .prop_map(
+                |(s, cache_control, st, usn, (al, location), (server, boot_id, config_id, search_port))| {
+                    SsdpMessage::SearchResponse {
+                        s,
+                        cache_control,
+                        st,
+                        usn,
+                        al,
+                        location,
+                        server,
+                        boot_id,
+                        config_id,
+                        search_port,
+                    }
+                }
+            ),
+        (
+            header_value(),
+            header_value(),
+            header_value(),
+            locations(),
+            proptest::option::of(header_value()),
+            upnp_headers(),
+        )
+            .prop_map(
+                |(host, nt, usn, (al, location), cache_control, (server, boot_id, config_id, search_port))| {
+                    SsdpMessage::NotifyAlive {
+                        host,
+                        nt,
+                        usn,
+                        al,
+                        location,
+                        cache_control,
+                        server,
+                        boot_id,
+                        config_id,
+                        search_port,
+                    }
+                }
+            ),
+        (
+            header_value(),
+            header_value(),
+            header_value(),
+            proptest::option::of(any::<u32>()),
+            proptest::option::of(any::<u32>()),
+        )
+            .prop_map(|(host, nt, usn, boot_id, config_id)| SsdpMessage::NotifyByebye {
+                host,
+                nt,
+                usn,
+                boot_id,
+                config_id,
+            }),
+        (
+            header_value(),
+            header_value(),
+            header_value(),
+            locations(),
+            upnp_headers(),
+            proptest::option::of(any::<u32>()),
+        )
+            .prop_map(
+                |(host, nt, usn, (al, location), (_, boot_id, config_id, search_port), next_boot_id)| {
+                    SsdpMessage::NotifyUpdate {
+                        host,
+                        nt,
+                        usn,
+                        al,
+                        location,
+                        boot_id,
+                        config_id,
+                        next_boot_id,
+                        search_port,
+                    }
+                }
+            ),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn test_roundtrip(message in ssdp_message()) {
+        prop_assert_eq!(Ok(message.clone()), SsdpMessage::parse(&message.to_bytes()));
+    }
+}
+
+#[test]
+fn test_render_msearch() {
+    let message = SsdpMessage::MSearch {
+        s: Some("uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a".to_string()),
+        host: "239.255.255.250:1900".to_string(),
+        st: "my:service".to_string(),
+        mx: Some(3),
+        user_agent: None,
+    };
+
+    assert_eq!(
+        "M-SEARCH * HTTP/1.1\r\nS: uuid:efef336d-fc25-4038-98f0-0217f6cc9e7a\r\nHost: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nST: my:service\r\nMX: 3\r\n\r\n",
+        message.to_string()
+    );
+}
+
+#[test]
+fn test_parse_notify_reordered_mixed_case() {
+    let notify = "NOTIFY * HTTP/1.1\r\n\
+        usn: uuid:some-service-uuid\r\n\
+        nts: ssdp:alive\r\n\
+        CACHE-CONTROL: max-age=1800\r\n\
+        al: <uuid:some-service-uuid><https://foo/bar>\r\n\
+        HOST: 239.255.255.250:1900\r\n\
+        nt: some:special:service\r\n\r\n";
+
+    assert_eq!(
+        Ok(SsdpMessage::NotifyAlive {
+            host: "239.255.255.250:1900".to_string(),
+            nt: "some:special:service".to_string(),
+            usn: "uuid:some-service-uuid".to_string(),
+            al: vec!["uuid:some-service-uuid".to_string(), "https://foo/bar".to_string()],
+            location: None,
+            cache_control: Some("max-age=1800".to_string()),
+            server: None,
+            boot_id: None,
+            config_id: None,
+            search_port: None,
+        }),
+        SsdpMessage::parse(notify.as_bytes())
+    );
+}
+
+#[test]
+fn test_parse_upnp_notify() {
+    // As sent by an off-the-shelf media server
+    let notify = "NOTIFY * HTTP/1.1\r\n\
+        HOST: 239.255.255.250:1900\r\n\
+        CACHE-CONTROL: max-age=1800\r\n\
+        LOCATION: http://192.168.1.10:8200/rootDesc.xml\r\n\
+        SERVER: Debian/12 DLNADOC/1.50 UPnP/1.0 MiniDLNA/1.3.0\r\n\
+        NT: urn:schemas-upnp-org:service:ContentDirectory:1\r\n\
+        USN: uuid:4d696e69-444c-164e-9d41-b827eb54e939::urn:schemas-upnp-org:service:ContentDirectory:1\r\n\
+        NTS: ssdp:alive\r\n\
+        OPT: \"http://schemas.upnp.org/upnp/1/0/\"; ns=01\r\n\
+        01-NLS: 1\r\n\
+        BOOTID.UPNP.ORG: 1\r\n\
+        CONFIGID.UPNP.ORG: 1337\r\n\r\n";
+
+    assert_eq!(
+        Ok(SsdpMessage::NotifyAlive {
+            host: "239.255.255.250:1900".to_string(),
+            nt: "urn:schemas-upnp-org:service:ContentDirectory:1".to_string(),
+            usn: "uuid:4d696e69-444c-164e-9d41-b827eb54e939::urn:schemas-upnp-org:service:ContentDirectory:1".to_string(),
+            al: vec![],
+            location: Some("http://192.168.1.10:8200/rootDesc.xml".to_string()),
+            cache_control: Some("max-age=1800".to_string()),
+            server: Some("Debian/12 DLNADOC/1.50 UPnP/1.0 MiniDLNA/1.3.0".to_string()),
+            boot_id: Some(1),
+            config_id: Some(1337),
+            search_port: None,
+        }),
+        SsdpMessage::parse(notify.as_bytes())
+    );
+}
+
+#[test]
+fn test_parse_invalid_upnp_headers() {
+    // As sent by a home router, with a CONFIGID that is no number
+    let response = "HTTP/1.1 200 OK\r\n\
+        CACHE-CONTROL: max-age=120\r\n\
+        ST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\
+        USN: uuid:fc4ec57e-b051-11db-88f8-0060085db3f6::urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\
+        EXT:\r\n\
+        SERVER: AsusWRT/386 UPnP/1.1 MiniUPnPd/2.2.0\r\n\
+        LOCATION: http://192.168.1.1:52869/rootDesc.xml\r\n\
+        OPT: \"http://schemas.upnp.org/upnp/1/0/\"; ns=01\r\n\
+        01-NLS: 1689421385\r\n\
+        BOOTID.UPNP.ORG: 1689421385\r\n\
+        CONFIGID.UPNP.ORG: 1.0\r\n\r\n";
+
+    assert_eq!(
+        Ok(SsdpMessage::SearchResponse {
+            s: None,
+            cache_control: Some("max-age=120".to_string()),
+            st: "urn:schemas-upnp-org:device:InternetGatewayDevice:1".to_string(),
+            usn: "uuid:fc4ec57e-b051-11db-88f8-0060085db3f6::urn:schemas-upnp-org:device:InternetGatewayDevice:1"
+                .to_string(),
+            al: vec![],
+            location: Some("http://192.168.1.1:52869/rootDesc.xml".to_string()),
+            server: Some("AsusWRT/386 UPnP/1.1 MiniUPnPd/2.2.0".to_string()),
+            boot_id: Some(1689421385),
+            config_id: None,
+            search_port: None,
+        }),
+        SsdpMessage::parse(response.as_bytes())
+    );
+
+    let byebye = "NOTIFY * HTTP/1.1\r\nHost: 239.255.255.250:1900\r\nNT: my:service\r\nNTS: ssdp:byebye\r\n\
+        USN: uuid:foo\r\nBOOTID.UPNP.ORG: -1\r\n\r\n";
+    assert!(matches!(
+        SsdpMessage::parse(byebye.as_bytes()),
+        Ok(SsdpMessage::NotifyByebye { boot_id: None, .. })
+    ));
+}
+
+#[test]
+fn test_cache_control_kept() {
+    let response = "HTTP/1.1 200 OK\r\n\
+        CACHE-CONTROL: public, max-age=1800, must-revalidate\r\n\
+        ST: some:special:service\r\n\
+        USN: uuid:some-service-uuid\r\n\
+        LOCATION: https://foo/bar\r\n\r\n";
+
+    let message = SsdpMessage::parse(response.as_bytes()).unwrap();
+    assert_eq!(Some(1800), message.max_age());
+
+    let rendered = String::from_utf8(message.to_bytes()).unwrap();
+    assert!(rendered.contains("Cache-Control: public, max-age=1800, must-revalidate\r\n"));
+}
+
+#[test]
+fn test_parse_errors() {
+    assert!(matches!(
+        SsdpMessage::parse(b"M-SEARCH * HTTP/1.1\r\nHost: 239.255.255.250:1900\r\n"),
+        Err(ParseError::Http(_))
+    ));
+
+    assert_eq!(
+        Err(ParseError::UnknownMethod("GET".to_string())),
+        SsdpMessage::parse(b"GET / HTTP/1.1\r\nHost: foo\r\n\r\n")
+    );
+
+    assert_eq!(
+        Err(ParseError::UnexpectedStatus(404)),
+        SsdpMessage::parse(b"HTTP/1.1 404 Not Found\r\n\r\n")
+    );
+
+    assert_eq!(
+        Err(ParseError::MissingHeader("AL")),
+        SsdpMessage::parse(b"HTTP/1.1 200 OK\r\nST: my:service\r\nUSN: uuid:foo\r\n\r\n")
+    );
+
+    assert_eq!(
+        Err(ParseError::MissingHeader("USN")),
+        SsdpMessage::parse(b"HTTP/1.1 200 OK\r\nST: my:service\r\nAL: <my:service><https://foo/bar>\r\n\r\n")
+    );
+
+    assert_eq!(
+        Err(ParseError::InvalidHeader("MX", "soon".to_string())),
+        SsdpMessage::parse(b"M-SEARCH * HTTP/1.1\r\nHost: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nST: ssdp:all\r\nMX: soon\r\n\r\n")
+    );
+
+    assert_eq!(
+        Err(ParseError::InvalidHeader("NTS", "ssdp:propchange".to_string())),
+        SsdpMessage::parse(b"NOTIFY * HTTP/1.1\r\nHost: 239.255.255.250:1900\r\nNT: my:service\r\nNTS: ssdp:propchange\r\nUSN: uuid:foo\r\n\r\n")
+    );
+}
@@ -1,14 +1,40 @@
-use std::net::Ipv4Addr;
+use std::future::Future;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 use log::debug;
 use log::trace;
+use rand::Rng;
 use tokio::net::UdpSocket;
+use tokio::sync::oneshot;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::task::JoinSet;
+use tokio::time::sleep;
+use tokio::time::sleep_until;
+use tokio::time::Instant;
 
+use crate::device::Device;
+use crate::http_helper::default_product_token;
+use crate::http_helper::generate_ssdp_alive;
+use crate::http_helper::generate_ssdp_byebye;
 use crate::http_helper::generate_ssdp_discover_answer;
+use crate::http_helper::parse_ssdp_discover;
+use crate::http_helper::UpnpHeaders;
+use crate::http_helper::DEFAULT_EXPIRATION;
+use crate::interface::resolve_interfaces;
+use crate::interface::Interface;
 use crate::socket_helper::join_socket;
+use crate::socket_helper::set_multicast_if;
+use crate::socket_helper::SocketBuilder;
+use crate::socket_helper::target_addr;
 use crate::MulticastAddr;
+use crate::Error;
+use crate::Protocol;
 use crate::SSDP_PORT;
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -53,117 +79,705 @@ pub struct ServiceDescription {
 
 /// The SSDP Service
 ///
-/// Call [Service::new] with [ServiceDescription] to create a new [Service]
+/// Call [Service::new] with [ServiceDescription] to create a new [Service].
+/// One [Service] listener can host any number of [ServiceDescription]s, see [Service::add_service].
 pub struct Service {
-    service_description: ServiceDescription,
+    /// All registered descriptions, identified by [ServiceDescription::usn_uri]
+    services: Mutex<Vec<ServiceDescription>>,
     // TODO we might want to hold a list of all Clients aswell
+
+    /// The socket and network while listening, used to announce changes to the registrations
+    listener: Mutex<Option<Listener>>,
+
+    /// How often each `NOTIFY` is sent in a row, to survive the loss of single UDP packets
+    announce_count: usize,
+
+    /// The flavour of SSDP sent
+    protocol: Protocol,
+
+    /// `SERVER` sent in [Protocol::Upnp] mode
+    server: String,
+
+    /// `BOOTID.UPNP.ORG` sent in [Protocol::Upnp] mode
+    boot_id: u32,
+
+    /// `CONFIGID.UPNP.ORG` sent in [Protocol::Upnp] mode
+    config_id: u32,
+
+    /// The interfaces to listen and announce on, empty lets the OS choose
+    interfaces: Vec<Interface>,
+
+    /// Options of the sockets
+    socket_builder: SocketBuilder,
+}
+
+/// Where a listening [Service] multicasts its `NOTIFY` messages
+#[derive(Clone)]
+struct Listener {
+    address: MulticastAddr,
+
+    /// A socket and target address per interface
+    senders: Vec<(Arc<UdpSocket>, SocketAddr)>,
 }
 
-// TODO when starting Service send NOTIFY ssdp:alive to Multicast
-// TODO when stopping Service send NOTIFY ssdp::byebye to Multicast
-// TODO make this permanently running and accept Signals signaling to stop etc.
+/// Picks the delay before answering a `M-SEARCH`
+///
+/// Spreads the answers of many Services evenly over the `MX` seconds the [crate::client::Client] waits.
+/// UPnP caps `MX` at 5 seconds. Requests without `MX` are answered right away.
+pub(crate) fn response_delay(mx: Option<u32>) -> Duration {
+    let Some(mx) = mx else {
+        return Duration::ZERO;
+    };
+
+    let max = Duration::from_secs(mx.min(5).into());
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+
+    rand::thread_rng().gen_range(Duration::ZERO..=max)
+}
+
+/// UPnP boot ids must increase with every boot and fit into 31 bits
+fn default_boot_id() -> u32 {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    (since_epoch & 0x7FFF_FFFF) as u32
+}
+
+/// Picks the pause between two repeats of the same `NOTIFY`
+///
+/// A few hundred milliseconds with jitter, so Services coming up at once don't keep colliding.
+pub(crate) fn repeat_delay() -> Duration {
+    rand::thread_rng().gen_range(Duration::from_millis(100)..Duration::from_millis(300))
+}
+
+/// Picks the time until the next `ssdp:alive` re-announcement
+///
+/// UPnP requires re-announcing at a randomly distributed interval of less than half the expiration,
+/// so this is somewhere between a quarter and a half of `expiration` seconds, but at least one second.
+pub(crate) fn announce_interval(expiration: u32) -> Duration {
+    let max = Duration::from_secs(expiration.into()) / 2;
+    let min = max / 2;
+
+    if max <= Duration::from_secs(1) {
+        return Duration::from_secs(1);
+    }
+
+    rand::thread_rng().gen_range(min..max).max(Duration::from_secs(1))
+}
 
 impl Service {
     /// Creates a new [Service]
     ///
     /// Requires a [ServiceDescription] to describe this Service
     pub fn new(service_description: ServiceDescription) -> Self {
+        Self::with_services(vec![service_description])
+    }
+
+    /// Creates a new [Service] announcing a UPnP [Device] tree
+    ///
+    /// Use together with [Protocol::Upnp], see [Device::service_descriptions].
+    pub fn from_device(device: &Device) -> Self {
+        Self::with_services(device.service_descriptions())
+    }
+
+    fn with_services(services: Vec<ServiceDescription>) -> Self {
         Service {
-            service_description,
+            services: Mutex::new(services),
+            listener: Mutex::new(None),
+            announce_count: 2,
+            protocol: Protocol::default(),
+            server: default_product_token(),
+            boot_id: default_boot_id(),
+            config_id: 1,
+            interfaces: vec![],
+            socket_builder: SocketBuilder::default(),
+        }
+    }
+
+    /// Changes the flavour of SSDP sent
+    ///
+    /// Defaults to [Protocol::Draft]. `M-SEARCH` requests are understood in both flavours either way,
+    /// but in [Protocol::Draft] mode only requests carrying the `S` header are answered.
+    pub fn set_protocol(&mut self, protocol: Protocol) -> &Self {
+        self.protocol = protocol;
+
+        self
+    }
+
+    /// Changes the `SERVER` sent in [Protocol::Upnp] mode
+    ///
+    /// UPnP expects `<OS>/<version> UPnP/2.0 <product>/<version>`
+    pub fn set_server(&mut self, server: String) -> &Self {
+        self.server = server;
+
+        self
+    }
+
+    /// Changes the `BOOTID.UPNP.ORG` sent in [Protocol::Upnp] mode
+    ///
+    /// Must increase every time the device comes up, defaults to the seconds since the UNIX epoch.
+    pub fn set_boot_id(&mut self, boot_id: u32) -> &Self {
+        self.boot_id = boot_id;
+
+        self
+    }
+
+    /// Changes the `CONFIGID.UPNP.ORG` sent in [Protocol::Upnp] mode
+    ///
+    /// Must change whenever the device description changes, defaults to `1`.
+    pub fn set_config_id(&mut self, config_id: u32) -> &Self {
+        self.config_id = config_id;
+
+        self
+    }
+
+    /// Changes the network interfaces to listen and announce on
+    ///
+    /// The multicast group is joined on each of them and every `NOTIFY` is sent out on each of them.
+    /// Defaults to none, letting the OS choose a single interface.
+    pub fn set_interfaces(&mut self, interfaces: Vec<Interface>) -> &Self {
+        self.interfaces = interfaces;
+
+        self
+    }
+
+    /// Changes the multicast TTL (`IP_MULTICAST_TTL`) or IPv6 hop limit (`IPV6_MULTICAST_HOPS`)
+    ///
+    /// Limits how many routers multicasts cross. Defaults to `2` as SSDP requires.
+    pub fn set_multicast_ttl(&mut self, ttl: u32) -> &Self {
+        self.socket_builder.multicast_ttl = Some(ttl);
+
+        self
+    }
+
+    /// Changes whether multicasts are looped back to this host (`IP_MULTICAST_LOOP`, `IPV6_MULTICAST_LOOP`)
+    ///
+    /// Defaults to `true`, so Clients on the same host see them.
+    pub fn set_multicast_loop(&mut self, multicast_loop: bool) -> &Self {
+        self.socket_builder.multicast_loop = Some(multicast_loop);
+
+        self
+    }
+
+    /// Changes the receive buffer size (`SO_RCVBUF`) of the sockets
+    ///
+    /// Defaults to the OS default. Raise it if many `M-SEARCH` requests arrive at once.
+    pub fn set_recv_buffer_size(&mut self, size: usize) -> &Self {
+        self.socket_builder.recv_buffer_size = Some(size);
+
+        self
+    }
+
+    /// Changes the send buffer size (`SO_SNDBUF`) of the sockets
+    ///
+    /// Defaults to the OS default.
+    pub fn set_send_buffer_size(&mut self, size: usize) -> &Self {
+        self.socket_builder.send_buffer_size = Some(size);
+
+        self
+    }
+
+    /// The UPnP headers to send, `None` in [Protocol::Draft] mode
+    fn upnp_headers(&self) -> Option<UpnpHeaders<'_>> {
+        match self.protocol {
+            Protocol::Draft => None,
+            Protocol::Upnp => Some(UpnpHeaders {
+                server: &self.server,
+                boot_id: self.boot_id,
+                config_id: self.config_id,
+                search_port: self.search_port(),
+            }),
         }
     }
 
-    async fn make_socket(&self) -> UdpSocket {
-        let local_addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, SSDP_PORT));
-        let s = {
-            use socket2::{Domain, Protocol, Socket, Type};
-            let s = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).unwrap();
-            s.set_reuse_address(true).unwrap();
-            s.set_nonblocking(true).unwrap();
-            s.bind(&SocketAddr::from(local_addr).into()).unwrap();
-            s
+    /// The port unicast `M-SEARCH` are received on, if listening on another port than [SSDP_PORT]
+    fn search_port(&self) -> Option<u16> {
+        let port = self.listener.lock().unwrap().as_ref()?.address.port();
+
+        (port != SSDP_PORT).then_some(port)
+    }
+
+    /// Changes how often each `NOTIFY` is sent in a row
+    ///
+    /// Defaults to `2`, as UPnP recommends sending each announcement more than once.
+    pub fn set_announce_count(&mut self, count: usize) -> &Self {
+        self.announce_count = count.max(1);
+
+        self
+    }
+
+    /// Returns all registered [ServiceDescription]s
+    pub fn get_services(&self) -> Vec<ServiceDescription> {
+        self.services.lock().unwrap().clone()
+    }
+
+    /// Registers another [ServiceDescription] on this listener
+    ///
+    /// While listening, `ssdp:alive` is multicasted for it right away.
+    /// A registration with the same [ServiceDescription::usn_uri] is replaced, like [Service::update_service] does.
+    pub async fn add_service(
+        &self,
+        service_description: ServiceDescription,
+    ) -> Result<(), Error> {
+        let previous = {
+            let mut services_guard = self.services.lock().unwrap();
+            match services_guard
+                .iter_mut()
+                .find(|registered| registered.usn_uri == service_description.usn_uri)
+            {
+                Some(registered) => Some(std::mem::replace(registered, service_description.clone())),
+                None => {
+                    services_guard.push(service_description.clone());
+                    None
+                }
+            }
+        };
+
+        match previous {
+            Some(previous) => self.announce_update(&previous, &service_description).await,
+            None => {
+                debug!("Added service {}", service_description.usn_uri);
+                self.announce_alive(&service_description).await
+            }
+        }
+    }
+
+    /// Replaces the registration with the same [ServiceDescription::usn_uri]
+    ///
+    /// While listening, `ssdp:alive` is multicasted with the new description. If the service type changed,
+    /// `ssdp:byebye` is multicasted for the old one first.
+    /// Returns the previous description, or `None` if nothing was registered under this USN.
+    pub async fn update_service(
+        &self,
+        service_description: ServiceDescription,
+    ) -> Result<Option<ServiceDescription>, Error> {
+        let previous = {
+            let mut services_guard = self.services.lock().unwrap();
+            let Some(registered) = services_guard
+                .iter_mut()
+                .find(|registered| registered.usn_uri == service_description.usn_uri)
+            else {
+                return Ok(None);
+            };
+
+            std::mem::replace(registered, service_description.clone())
         };
-        let socket = Arc::new(UdpSocket::from_std(s.into()).unwrap());
-        UdpSocket::bind(local_addr).await.unwrap()
+        self.announce_update(&previous, &service_description).await?;
+
+        Ok(Some(previous))
+    }
+
+    /// Multicasts the replacement of `previous` by `service_description`, if listening
+    async fn announce_update(
+        &self,
+        previous: &ServiceDescription,
+        service_description: &ServiceDescription,
+    ) -> Result<(), Error> {
+        debug!("Updated service {}", service_description.usn_uri);
+
+        if previous.service_type_uri != service_description.service_type_uri {
+            self.announce_byebye(previous).await?;
+        }
+        self.announce_alive(service_description).await
+    }
+
+    /// Removes the registration with the given [ServiceDescription::usn_uri]
+    ///
+    /// While listening, `ssdp:byebye` is multicasted for it right away.
+    /// Returns the removed description, or `None` if nothing was registered under this USN.
+    pub async fn remove_service(
+        &self,
+        usn_uri: &str,
+    ) -> Result<Option<ServiceDescription>, Error> {
+        let removed = {
+            let mut services_guard = self.services.lock().unwrap();
+            let Some(index) = services_guard
+                .iter()
+                .position(|registered| registered.usn_uri == usn_uri)
+            else {
+                return Ok(None);
+            };
+
+            services_guard.remove(index)
+        };
+        debug!("Removed service {}", removed.usn_uri);
+
+        self.announce_byebye(&removed).await?;
+
+        Ok(Some(removed))
+    }
+
+    /// Registers all [ServiceDescription]s of a UPnP [Device] tree, see [Service::add_service]
+    pub async fn add_device(&self, device: &Device) -> Result<(), Error> {
+        for service_description in device.service_descriptions() {
+            self.add_service(service_description).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes all [ServiceDescription]s of a UPnP [Device] tree, see [Service::remove_service]
+    pub async fn remove_device(&self, device: &Device) -> Result<(), Error> {
+        for service_description in device.service_descriptions() {
+            self.remove_service(&service_description.usn_uri).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Starts listening in the background
+    ///
+    /// Same as [Service::listen_until], stopped by [ServiceHandle::shutdown] or by dropping the handle.
+    /// To stop on a `CancellationToken` or any other signal instead, pass `token.cancelled()` to [Service::listen_until].
+    ///
+    /// Must be called from within a tokio runtime
+    pub fn start(self, address: MulticastAddr) -> ServiceHandle {
+        let service = Arc::new(self);
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        let (listening, ready) = watch::channel(false);
+
+        let listener = service.clone();
+        let task = tokio::spawn(async move { listener.run(address, stopped, Some(listening)).await });
+
+        ServiceHandle {
+            service,
+            shutdown,
+            ready,
+            task,
+        }
     }
 
     /// Opens the listener
     ///
     /// This process is blocking so best to start it in its own thread
-    pub async fn listen(&self, address: MulticastAddr) -> Result<(), Box<dyn std::error::Error>> {
-        let socket = Arc::new(self.make_socket().await);
+    ///
+    /// Multicasts `ssdp:alive` once the listener is up. Use [Service::listen_until] to be able to stop it gracefully.
+    ///
+    /// Besides the multicasted ones, `M-SEARCH` sent directly to the SSDP port are answered as well,
+    /// see [crate::client::Client::search_unicast]. The SSDP port is shared with `SO_REUSEPORT`, so with several
+    /// Services on this host the OS hands each unicast `M-SEARCH` to one of them only.
+    pub async fn listen(&self, address: MulticastAddr) -> Result<(), Error> {
+        self.listen_until(address, std::future::pending::<()>()).await
+    }
 
-        join_socket(&address, socket.clone())?;
+    /// Opens the listener until `shutdown` completes
+    ///
+    /// Multicasts `ssdp:alive` once the listener is up and `ssdp:byebye` after `shutdown` completed,
+    /// so [crate::client::Client]s learn about this Service without searching for it.
+    /// While listening, `ssdp:alive` is re-sent at a random interval of less than half of [ServiceDescription::expiration].
+    ///
+    /// Fails if the sockets can't be set up. Later failures to receive or send a single message are only logged.
+    ///
+    /// ```no_run
+    /// # use simple_ssdp::service::{Service, ServiceDescription};
+    /// # use simple_ssdp::{Error, MulticastAddr};
+    /// # async fn run(service: Service) -> Result<(), Error> {
+    /// // Say byebye after an hour
+    /// let shutdown = tokio::time::sleep(std::time::Duration::from_secs(3600));
+    /// service.listen_until(MulticastAddr::V4, shutdown).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn listen_until(
+        &self,
+        address: MulticastAddr,
+        shutdown: impl Future,
+    ) -> Result<(), Error> {
+        self.run(address, shutdown, None).await
+    }
+
+    /// Same as [Service::listen_until], setting `listening` once the sockets are set up
+    async fn run(
+        &self,
+        address: MulticastAddr,
+        shutdown: impl Future,
+        listening: Option<watch::Sender<bool>>,
+    ) -> Result<(), Error> {
+        let interfaces = resolve_interfaces(&self.interfaces)?;
+        let socket = Arc::new(self.socket_builder.bind_ssdp(&address)?);
+
+        let mut senders = vec![];
+        if interfaces.is_empty() {
+            join_socket(&address, socket.clone(), None)?;
+            senders.push((socket.clone(), target_addr(&address, None)));
+        }
+        for interface in &interfaces {
+            join_socket(&address, socket.clone(), Some(interface))?;
+
+            let sender = self.socket_builder.bind_any(&address)?;
+            set_multicast_if(&address, &sender, interface)?;
+            senders.push((Arc::new(sender), target_addr(&address, Some(interface))));
+        }
+
+        let listener = Listener { address, senders };
+        *self.listener.lock().unwrap() = Some(listener.clone());
+        if let Some(listening) = listening {
+            let _ = listening.send(true);
+        }
+
+        self.serve(&socket, shutdown).await;
+        *self.listener.lock().unwrap() = None;
+
+        debug!("Stop listening for SSDP discovery messages...");
+
+        let byebyes: Vec<String> = self
+            .get_services()
+            .iter()
+            .map(|service_description| {
+                generate_ssdp_byebye(service_description, &address, self.upnp_headers().as_ref())
+            })
+            .collect();
+
+        if let Err(e) = self.notify(&listener, &byebyes).await {
+            debug!("Could not say byebye: {}", e);
+        }
+
+        Ok(())
+    }
+
+    /// Announces all registrations and answers `M-SEARCH` requests until `shutdown` completes
+    ///
+    /// Failing to receive, announce or answer is logged and skipped, so a single bad packet doesn't stop the Service.
+    async fn serve(
+        &self,
+        socket: &Arc<UdpSocket>,
+        shutdown: impl Future,
+    ) {
+        if let Err(e) = self.announce_all_alive().await {
+            debug!("Could not announce: {}", e);
+        }
+        let mut next_announcement = Instant::now() + self.announce_interval();
 
         // Create a buffer to store the received data
         let mut buf = vec![0; 1024];
 
+        // Answers waiting for their delay, see response_delay
+        let mut pending_answers = JoinSet::new();
+
         debug!("Start listening for SSDP discovery messages...");
 
+        tokio::pin!(shutdown);
+
         // Listen for discovery requests and respond
         loop {
-            let (len, addr) = socket.recv_from(&mut buf).await?;
-            let copy = buf.clone();
-            trace!(
-                "Received {} bytes from {}: {:#?}",
-                len,
-                addr,
-                String::from_utf8(copy).unwrap().replace('\0', "")
-            );
+            tokio::select! {
+                _ = &mut shutdown => break,
+                _ = sleep_until(next_announcement) => {
+                    if let Err(e) = self.announce_all_alive().await {
+                        debug!("Could not announce: {}", e);
+                    }
+                    next_announcement = Instant::now() + self.announce_interval();
+                }
+                received = socket.recv_from(&mut buf) => match received {
+                    Ok((len, addr)) => self.answer(socket, &buf[..len], addr, &mut pending_answers).await,
+                    Err(e) => debug!("Could not receive: {}", e),
+                },
+                Some(_) = pending_answers.join_next(), if !pending_answers.is_empty() => {}
+            }
+        }
 
-            let mut headers = [httparse::EMPTY_HEADER; 64];
-            let mut req = httparse::Request::new(&mut headers);
+        // Answers still waiting would keep the socket open
+        pending_answers.shutdown().await;
+    }
 
-            if req.parse(&buf[..len]).is_err() {
-                trace!("Could not parse request to HTTP");
-                continue;
-            }
+    /// The time until all registrations are re-announced, based on the shortest [ServiceDescription::expiration]
+    fn announce_interval(&self) -> Duration {
+        let expiration = self
+            .services
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|service_description| service_description.expiration)
+            .min()
+            .unwrap_or(DEFAULT_EXPIRATION);
 
-            if req.method.is_none() || req.method.is_some_and(|method| method != "M-SEARCH") {
-                trace!("Request is not M-SEARCH");
-                continue;
-            }
+        announce_interval(expiration)
+    }
 
-            // TODO right now this depends on the order of the header - as I only plan using client and service I don't really care
-            let man = req.headers.get(2);
-            if man.is_none()
-                || man.is_some_and(|man| String::from_utf8_lossy(man.value) != "\"ssdp:discover\"")
-            {
-                trace!("Request uses wrong MAN header");
-                continue;
-            }
+    /// Multicasts `ssdp:alive` for a registration, if listening
+    async fn announce_alive(
+        &self,
+        service_description: &ServiceDescription,
+    ) -> Result<(), Error> {
+        let Some(listener) = self.listener.lock().unwrap().clone() else {
+            return Ok(());
+        };
 
-            let st = req.headers.get(3);
-            if st.is_none()
-                || st.is_some_and(|st| -> bool {
-                    let st = String::from_utf8_lossy(st.value);
+        let alive = generate_ssdp_alive(
+            service_description,
+            &listener.address,
+            self.upnp_headers().as_ref(),
+        );
+        self.notify(&listener, &[alive]).await
+    }
 
-                    st != "ssdp:all" && st != self.service_description.service_type_uri
-                })
-            {
-                trace!("ST header that's not interesting for us submitted");
-                continue;
+    /// Multicasts `ssdp:alive` for all registrations, if listening
+    async fn announce_all_alive(&self) -> Result<(), Error> {
+        let Some(listener) = self.listener.lock().unwrap().clone() else {
+            return Ok(());
+        };
+
+        let alives: Vec<String> = self
+            .get_services()
+            .iter()
+            .map(|service_description| {
+                generate_ssdp_alive(service_description, &listener.address, self.upnp_headers().as_ref())
+            })
+            .collect();
+        self.notify(&listener, &alives).await
+    }
+
+    /// Multicasts `ssdp:byebye` for a registration, if listening
+    async fn announce_byebye(
+        &self,
+        service_description: &ServiceDescription,
+    ) -> Result<(), Error> {
+        let Some(listener) = self.listener.lock().unwrap().clone() else {
+            return Ok(());
+        };
+
+        let byebye = generate_ssdp_byebye(
+            service_description,
+            &listener.address,
+            self.upnp_headers().as_ref(),
+        );
+        self.notify(&listener, &[byebye]).await
+    }
+
+    /// Multicasts `NOTIFY` messages [Service::set_announce_count] times on every interface
+    ///
+    /// The repeats are spaced by [repeat_delay], so a short burst of packet loss doesn't swallow all of them.
+    /// Each repeat sends all `messages` at once, so many registrations don't hold up each other.
+    async fn notify(
+        &self,
+        listener: &Listener,
+        messages: &[String],
+    ) -> Result<(), Error> {
+        for repeat in 0..self.announce_count {
+            if repeat > 0 {
+                sleep(repeat_delay()).await;
             }
 
-            let s = req.headers.get(0);
-            match s {
-                None => {
-                    trace!("S was not submitted");
-                    continue;
+            for message in messages {
+                for (socket, multicast_addr) in &listener.senders {
+                    socket
+                        .send_to(message.as_bytes(), multicast_addr)
+                        .await
+                        .map_err(Error::Send)?;
+                    trace!("Send SSDP notify {:#?} to {}", message, multicast_addr);
                 }
-                Some(s) => {
-                    let s = String::from_utf8_lossy(s.value);
-                    let resp_msg =
-                        generate_ssdp_discover_answer(&self.service_description, s.to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Answers a received message if it's a `M-SEARCH` request for any registered [ServiceDescription]
+    ///
+    /// Each answer is sent after a random delay of up to `MX` seconds in the background.
+    async fn answer(
+        &self,
+        socket: &Arc<UdpSocket>,
+        buf: &[u8],
+        addr: SocketAddr,
+        pending_answers: &mut JoinSet<()>,
+    ) {
+        trace!(
+            "Received {} bytes from {}: {:#?}",
+            buf.len(),
+            addr,
+            String::from_utf8_lossy(buf)
+        );
+
+        let Some((s, st, mx)) = parse_ssdp_discover(buf) else {
+            return;
+        };
+
+        if s.is_none() && self.protocol == Protocol::Draft {
+            trace!("S was not submitted");
+            return;
+        }
+
+        let matching: Vec<ServiceDescription> = self
+            .get_services()
+            .into_iter()
+            .filter(|service_description| {
+                st == "ssdp:all" || st == service_description.service_type_uri
+            })
+            .collect();
 
-                    socket.send_to(resp_msg.as_bytes(), &addr).await?;
-                    trace!("Send SSDP response {:#?} to {}", resp_msg, addr);
+        if matching.is_empty() {
+            trace!("ST header that's not interesting for us submitted");
+            return;
+        }
+
+        // One answer per registration, each with its own delay
+        for service_description in matching {
+            let resp_msg = generate_ssdp_discover_answer(
+                &service_description,
+                s.clone(),
+                self.upnp_headers().as_ref(),
+            );
+
+            let delay = response_delay(mx);
+            if delay.is_zero() {
+                match socket.send_to(resp_msg.as_bytes(), &addr).await {
+                    Ok(_) => trace!("Send SSDP response {:#?} to {}", resp_msg, addr),
+                    Err(e) => debug!("Could not send SSDP response to {}: {}", addr, e),
                 }
-            }
-            if s.is_none() {
-                trace!("S was not submitted");
+
                 continue;
             }
+
+            let socket = socket.clone();
+            pending_answers.spawn(async move {
+                sleep(delay).await;
+
+                match socket.send_to(resp_msg.as_bytes(), &addr).await {
+                    Ok(_) => trace!("Send SSDP response {:#?} to {} after {:?}", resp_msg, addr, delay),
+                    Err(e) => debug!("Could not send SSDP response to {}: {}", addr, e),
+                }
+            });
+        }
+    }
+}
+
+/// A [Service] listening in the background, see [Service::start]
+pub struct ServiceHandle {
+    service: Arc<Service>,
+    shutdown: oneshot::Sender<()>,
+    ready: watch::Receiver<bool>,
+    task: JoinHandle<Result<(), Error>>,
+}
+
+impl ServiceHandle {
+    /// The running [Service], e.g. to change its registrations with [Service::add_service]
+    pub fn service(&self) -> &Service {
+        &self.service
+    }
+
+    /// Waits until the Service listens, so `M-SEARCH` sent from now on are answered
+    ///
+    /// Returns `false` if it failed to set up its sockets, [ServiceHandle::shutdown] returns the error.
+    pub async fn ready(&self) -> bool {
+        self.ready.clone().wait_for(|ready| *ready).await.is_ok()
+    }
+
+    /// Stops listening
+    ///
+    /// Multicasts `ssdp:byebye` for every registration and returns once the socket is closed.
+    /// Returns the error the listener stopped with, if it failed before.
+    pub async fn shutdown(self) -> Result<(), Error> {
+        let _ = self.shutdown.send(());
+
+        match self.task.await {
+            Ok(result) => result,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(_) => Ok(()),
         }
     }
 }
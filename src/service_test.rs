@@ -0,0 +1,83 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::task::JoinSet;
+
+use crate::service::announce_interval;
+use crate::service::repeat_delay;
+use crate::service::response_delay;
+use crate::service::Service;
+use crate::service::ServiceDescription;
+
+#[test]
+fn test_announce_interval() {
+    for _ in 0..100 {
+        let interval = announce_interval(1800);
+
+        assert!(interval >= Duration::from_secs(450));
+        assert!(interval < Duration::from_secs(900));
+    }
+}
+
+#[test]
+fn test_announce_interval_short_expiration() {
+    assert_eq!(Duration::from_secs(1), announce_interval(0));
+    assert_eq!(Duration::from_secs(1), announce_interval(2));
+
+    for _ in 0..100 {
+        let interval = announce_interval(3);
+
+        assert!(interval >= Duration::from_secs(1));
+        assert!(interval <= Duration::from_millis(1500));
+    }
+}
+
+#[test]
+fn test_repeat_delay() {
+    for _ in 0..100 {
+        let delay = repeat_delay();
+
+        assert!(delay >= Duration::from_millis(100));
+        assert!(delay < Duration::from_millis(300));
+    }
+}
+
+#[test]
+fn test_response_delay() {
+    assert_eq!(Duration::ZERO, response_delay(None));
+    assert_eq!(Duration::ZERO, response_delay(Some(0)));
+
+    for _ in 0..100 {
+        assert!(response_delay(Some(2)) <= Duration::from_secs(2));
+    }
+
+    // UPnP caps MX at 5 seconds
+    for _ in 0..100 {
+        assert!(response_delay(Some(120)) <= Duration::from_secs(5));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_add_service_same_usn() {
+    let description = |location: usize| ServiceDescription {
+        usn_uri: "uuid:some-service-uuid".to_string(),
+        service_type_uri: "some:special:service".to_string(),
+        expiration: 100,
+        location: format!("https://foo/{}", location),
+    };
+    let service = Arc::new(Service::new(ServiceDescription {
+        usn_uri: "uuid:other-service-uuid".to_string(),
+        ..description(0)
+    }));
+
+    let mut adding = JoinSet::new();
+    for location in 0..32 {
+        let service = service.clone();
+        adding.spawn(async move { service.add_service(description(location)).await.unwrap() });
+    }
+    while adding.join_next().await.is_some() {}
+
+    let registered = service.get_services();
+    assert_eq!(2, registered.len());
+    assert_eq!(1, registered.iter().filter(|registered| registered.usn_uri == "uuid:some-service-uuid").count());
+}
@@ -1,24 +1,203 @@
+use std::net::IpAddr;
 use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::net::SocketAddrV6;
 use std::sync::Arc;
 use tokio::net::UdpSocket;
+use crate::interface::NetworkInterface;
+use crate::Error;
 use crate::MulticastAddr;
 
-pub(crate) fn join_socket(address: &MulticastAddr, socket: Arc<UdpSocket>) -> Result<(), Box<dyn std::error::Error>> {
+/// The multicast TTL SSDP requires
+///
+/// UPnP 1.0 recommended 4, UPnP 1.1 and later settled on 2 as well.
+pub(crate) static DEFAULT_MULTICAST_TTL: u32 = 2;
+
+/// Creates the sockets of [crate::client::Client] and [crate::service::Service]
+///
+/// Options left at `None` keep the defaults of the OS.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SocketBuilder {
+    /// `IP_MULTICAST_TTL` or `IPV6_MULTICAST_HOPS`
+    pub(crate) multicast_ttl: Option<u32>,
+
+    /// `IP_MULTICAST_LOOP` or `IPV6_MULTICAST_LOOP`
+    pub(crate) multicast_loop: Option<bool>,
+
+    /// `SO_RCVBUF`
+    pub(crate) recv_buffer_size: Option<usize>,
+
+    /// `SO_SNDBUF`
+    pub(crate) send_buffer_size: Option<usize>,
+}
+
+impl Default for SocketBuilder {
+    /// Multicasts with [DEFAULT_MULTICAST_TTL] and loops them back, so co-hosted Clients see co-hosted Services
+    fn default() -> Self {
+        Self {
+            multicast_ttl: Some(DEFAULT_MULTICAST_TTL),
+            multicast_loop: Some(true),
+            recv_buffer_size: None,
+            send_buffer_size: None,
+        }
+    }
+}
+
+impl SocketBuilder {
+    /// Binds the SSDP port of `address` so multicasted messages are received
+    ///
+    /// The port is shared with other SSDP stacks on this host, like minissdpd, using `SO_REUSEADDR` and `SO_REUSEPORT`.
+    /// Opens an IPv6 socket for the IPv6 variants of [MulticastAddr], which only accepts IPv6,
+    /// so an IPv4 and an IPv6 socket may listen at the same time.
+    ///
+    /// Must be called from within a tokio runtime
+    pub(crate) fn bind_ssdp(&self, address: &MulticastAddr) -> Result<UdpSocket, Error> {
+        self.bind_multicast(address, address.port(), true)
+    }
+
+    /// Binds a random port of the IP version of `address`
+    ///
+    /// Must be called from within a tokio runtime
+    pub(crate) fn bind_any(&self, address: &MulticastAddr) -> Result<UdpSocket, Error> {
+        self.bind_multicast(address, 0, false)
+    }
+
+    /// Binds a random port to send unicast messages to `target` from
+    ///
+    /// Must be called from within a tokio runtime
+    pub(crate) fn bind_unicast(&self, target: &SocketAddr) -> Result<UdpSocket, Error> {
+        self.bind(SocketAddr::new(unspecified(target.is_ipv4()), 0), false, None)
+    }
+
+    /// Binds `port` to send to and receive from `address`, multicasting from `127.0.0.1` for [MulticastAddr::Loopback]
+    fn bind_multicast(&self, address: &MulticastAddr, port: u16, reuse: bool) -> Result<UdpSocket, Error> {
+        let local_addr = SocketAddr::new(unspecified(address.is_v4()), port);
+        let multicast_if = (address == &MulticastAddr::Loopback).then_some(Ipv4Addr::LOCALHOST);
+
+        self.bind(local_addr, reuse, multicast_if)
+    }
+
+    fn bind(&self, local_addr: SocketAddr, reuse: bool, multicast_if: Option<Ipv4Addr>) -> Result<UdpSocket, Error> {
+        use socket2::{Domain, Protocol, Socket, Type};
+
+        let s = Socket::new(Domain::for_address(local_addr), Type::DGRAM, Some(Protocol::UDP)).map_err(Error::Bind)?;
+
+        if local_addr.is_ipv6() {
+            s.set_only_v6(true).map_err(Error::Bind)?;
+        }
+        if reuse {
+            s.set_reuse_address(true).map_err(Error::Bind)?;
+            #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))))]
+            s.set_reuse_port(true).map_err(Error::Bind)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            s.set_recv_buffer_size(size).map_err(Error::Bind)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            s.set_send_buffer_size(size).map_err(Error::Bind)?;
+        }
+        if let Some(ttl) = self.multicast_ttl {
+            match local_addr.is_ipv4() {
+                true => s.set_multicast_ttl_v4(ttl),
+                false => s.set_multicast_hops_v6(ttl),
+            }
+            .map_err(Error::Bind)?;
+        }
+        if let Some(multicast_if) = multicast_if {
+            s.set_multicast_if_v4(&multicast_if).map_err(Error::Bind)?;
+        }
+        if let Some(multicast_loop) = self.multicast_loop {
+            match local_addr.is_ipv4() {
+                true => s.set_multicast_loop_v4(multicast_loop),
+                false => s.set_multicast_loop_v6(multicast_loop),
+            }
+            .map_err(Error::Bind)?;
+        }
+
+        s.set_nonblocking(true).map_err(Error::Bind)?;
+        s.bind(&local_addr.into()).map_err(Error::Bind)?;
+
+        UdpSocket::from_std(s.into()).map_err(Error::Bind)
+    }
+}
+
+/// `0.0.0.0` or `::`, depending on the IP version
+fn unspecified(v4: bool) -> IpAddr {
+    if v4 {
+        Ipv4Addr::UNSPECIFIED.into()
+    } else {
+        Ipv6Addr::UNSPECIFIED.into()
+    }
+}
+
+/// The address to send multicasted messages to
+///
+/// IPv6 link-local multicast needs to know the interface to send on, which is given by its index as scope id.
+/// Without an `interface` the scope id of a [MulticastAddr::Custom] one is kept, otherwise the OS chooses.
+pub(crate) fn target_addr(address: &MulticastAddr, interface: Option<&NetworkInterface>) -> SocketAddr {
+    match address.socket_addr() {
+        SocketAddr::V6(addr) => {
+            let scope_id = interface.map_or(address.scope_id(), |interface| interface.index);
+            SocketAddrV6::new(*addr.ip(), addr.port(), 0, scope_id).into()
+        }
+        addr => addr,
+    }
+}
+
+/// Joins the multicast group of `address`
+///
+/// - `interface` - The interface to join on, without one the scope id of a [MulticastAddr::Custom] one, if given,
+///   or else the OS chooses
+pub(crate) fn join_socket(address: &MulticastAddr, socket: Arc<UdpSocket>, interface: Option<&NetworkInterface>) -> Result<(), Error> {
     // Join the multicast group
-    
-    if address == &MulticastAddr::Loopback {
+
+    // Unicast addresses like `[::1]:41900` are sent to directly
+    if !address.get_ip().is_multicast() {
         return Ok(());
     }
-    
+
     if address.is_v4() {
         socket.join_multicast_v4(
             address.get_v4().expect("We just checked that it's v4"),
-            Ipv4Addr::UNSPECIFIED,
-        )?;
+            interface_v4(address, interface)?,
+        ).map_err(Error::Join)?;
+    } else {
+        socket.join_multicast_v6(
+            &address.get_v6().expect("We just checked that it's v6"),
+            interface.map_or(address.scope_id(), |interface| interface.index),
+        ).map_err(Error::Join)?;
+    }
+
+    Ok(())
+}
+
+/// Sends all multicasts of `socket` from the given interface (`IP_MULTICAST_IF`)
+pub(crate) fn set_multicast_if(address: &MulticastAddr, socket: &UdpSocket, interface: &NetworkInterface) -> Result<(), Error> {
+    // Unicast addresses like `[::1]:41900` are sent to directly
+    if !address.get_ip().is_multicast() {
+        return Ok(());
+    }
+
+    let socket = socket2::SockRef::from(socket);
+    if address.is_v4() {
+        socket.set_multicast_if_v4(&interface_v4(address, Some(interface))?).map_err(Error::Join)?;
     } else {
-        socket
-            .join_multicast_v6(&address.get_v6().expect("We just checked that it's v6"), 0)?;
+        socket.set_multicast_if_v6(interface.index).map_err(Error::Join)?;
     }
-    
+
     Ok(())
-}
\ No newline at end of file
+}
+
+/// The IPv4 address identifying `interface`
+///
+/// Without an `interface` this is `127.0.0.1` for [MulticastAddr::Loopback], otherwise `0.0.0.0` to let the OS choose
+fn interface_v4(address: &MulticastAddr, interface: Option<&NetworkInterface>) -> Result<Ipv4Addr, Error> {
+    match interface {
+        Some(interface) => interface
+            .v4()
+            .ok_or_else(|| Error::Interface(format!("{} has no IPv4 address", interface.name))),
+        None if address == &MulticastAddr::Loopback => Ok(Ipv4Addr::LOCALHOST),
+        None => Ok(Ipv4Addr::UNSPECIFIED),
+    }
+}
@@ -0,0 +1,93 @@
+use std::net::Ipv4Addr;
+
+use socket2::SockRef;
+
+use crate::socket_helper::target_addr;
+use crate::socket_helper::SocketBuilder;
+use crate::MulticastAddr;
+
+#[tokio::test]
+async fn test_socket_builder_options() {
+    let builder = SocketBuilder {
+        multicast_ttl: Some(4),
+        multicast_loop: Some(false),
+        recv_buffer_size: Some(65536),
+        send_buffer_size: None,
+    };
+
+    let socket = builder.bind_any(&MulticastAddr::V4).unwrap();
+    let socket = SockRef::from(&socket);
+
+    assert_eq!(4, socket.multicast_ttl_v4().unwrap());
+    assert!(!socket.multicast_loop_v4().unwrap());
+    assert!(socket.recv_buffer_size().unwrap() >= 65536);
+}
+
+#[tokio::test]
+async fn test_socket_builder_defaults() {
+    let socket = SocketBuilder::default().bind_any(&MulticastAddr::V4).unwrap();
+    let socket = SockRef::from(&socket);
+
+    assert_eq!(2, socket.multicast_ttl_v4().unwrap());
+    assert!(socket.multicast_loop_v4().unwrap());
+}
+
+#[tokio::test]
+async fn test_socket_builder_v6_hops() {
+    let builder = SocketBuilder {
+        multicast_ttl: Some(5),
+        ..SocketBuilder::default()
+    };
+
+    let Ok(socket) = builder.bind_any(&MulticastAddr::V6LinkLocal) else {
+        // No IPv6 on this host
+        return;
+    };
+    let socket = SockRef::from(&socket);
+
+    assert_eq!(5, socket.multicast_hops_v6().unwrap());
+    assert!(socket.multicast_loop_v6().unwrap());
+}
+
+#[tokio::test]
+async fn test_socket_builder_shares_ssdp_port() {
+    let builder = SocketBuilder::default();
+
+    let first = builder.bind_ssdp(&MulticastAddr::V4).unwrap();
+    let second = builder.bind_ssdp(&MulticastAddr::V4).unwrap();
+
+    assert_eq!(1900, first.local_addr().unwrap().port());
+    assert_eq!(1900, second.local_addr().unwrap().port());
+}
+
+#[tokio::test]
+async fn test_socket_builder_loopback() {
+    let socket = SocketBuilder::default().bind_ssdp(&MulticastAddr::Loopback).unwrap();
+    let socket = SockRef::from(&socket);
+
+    assert_eq!(Ipv4Addr::LOCALHOST, socket.multicast_if_v4().unwrap());
+    assert!(socket.multicast_loop_v4().unwrap());
+}
+
+#[tokio::test]
+async fn test_socket_builder_bind_unicast() {
+    let builder = SocketBuilder::default();
+
+    let socket = builder.bind_unicast(&"127.0.0.1:1900".parse().unwrap()).unwrap();
+    assert!(socket.local_addr().unwrap().is_ipv4());
+
+    let Ok(socket) = builder.bind_unicast(&"[::1]:1900".parse().unwrap()) else {
+        // No IPv6 on this host
+        return;
+    };
+    assert!(socket.local_addr().unwrap().is_ipv6());
+}
+
+#[test]
+fn test_target_addr_keeps_custom_scope_id() {
+    let address = MulticastAddr::Custom("[ff02::c%3]:41900".parse().unwrap());
+
+    assert_eq!("[ff02::c%3]:41900".parse::<std::net::SocketAddr>().unwrap(), target_addr(&address, None));
+    // The Host header never carries the scope id
+    assert_eq!("[ff02::c]:41900", address.socket_addr().to_string());
+}
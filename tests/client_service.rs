@@ -1,48 +1,38 @@
-use std::io::Error;
+mod common;
+
 use std::time::Duration;
-use ssdp::client::Client;
-use ssdp::service::Service;
-use ssdp::service::ServiceDescription;
-use ssdp::MulticastAddr;
+use simple_ssdp::service::Service;
+use simple_ssdp::service::ServiceDescription;
+use common::client;
+use common::private_group;
+use common::some_service;
+use common::start;
 
 #[tokio::test]
 /// Spinning up a client and a service on localhost to test the connection
 /// 
 /// This takes a while to reach the client's timeout.
 async fn test_client_and_service_communication() {
-    async fn listen() -> Result<(), Error> {
-        let desc = ServiceDescription {
-            usn_uri: "uuid:some-service-uuid".to_string(),
-            service_type_uri: "some:special:service".to_string(),
-            expiration: 100,
-            location: "https://foo/bar".to_string(),
-        };
-
-        let service = Service::new(desc);
-        let _ = service.listen(MulticastAddr::Loopback).await;
-
-        Ok(())
-    }
-
-    let thread_listen = tokio::spawn(async move { listen().await });
+    let address = private_group(41901);
+    let service = start(Service::new(some_service()), address).await;
 
-    let mut client = Client::default();
+    let mut client = client();
     client
         .set_timeout(Duration::from_millis(500));
 
     client
         .discover("uuid:some-client-uuid".to_string(),
-                  MulticastAddr::Loopback,
+                  address,
                   "some:special:service".to_string())
         .await.unwrap();
 
-    thread_listen.abort();
+    service.shutdown().await.unwrap();
     
     let expected: Vec<ServiceDescription> = vec![ServiceDescription{
         usn_uri: "uuid:some-service-uuid".to_string(),
         service_type_uri: "some:special:service".to_string(),
         expiration: 100,
-        location: "<some:special:service><https://foo/bar>".to_string(),
+        location: "https://foo/bar".to_string(),
     }];
     
     assert_eq!(expected, client.get_services());
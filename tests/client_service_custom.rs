@@ -0,0 +1,25 @@
+mod common;
+
+use std::time::Duration;
+use simple_ssdp::service::Service;
+use simple_ssdp::MulticastAddr;
+use common::client;
+use common::some_service;
+use common::start;
+
+#[tokio::test]
+/// Client and Service meet in a private group on a private port, clear of any SSDP daemon on port 1900
+async fn test_client_and_service_custom_group() {
+    let address = MulticastAddr::Custom("239.255.77.77:41900".parse().unwrap());
+    let service = start(Service::new(some_service()), address).await;
+
+    let mut client = client();
+    client.set_timeout(Duration::from_millis(500));
+    client
+        .discover("uuid:some-client-uuid".to_string(), address, "some:special:service".to_string())
+        .await.unwrap();
+
+    service.shutdown().await.unwrap();
+
+    assert_eq!(vec![some_service()], client.get_services());
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use std::time::Duration;
+use simple_ssdp::service::Service;
+use common::client;
+use common::private_group;
+use common::private_v6;
+use common::some_service;
+use common::start;
+
+#[tokio::test]
+/// A Service reachable via IPv4 and IPv6 is found once, at both addresses
+async fn test_client_discover_scopes() {
+    let addresses = [private_group(41902), private_v6(41902)];
+    let mut services = vec![];
+    for address in addresses {
+        services.push(start(Service::new(some_service()), address).await);
+    }
+
+    let mut client = client();
+    client.set_timeout(Duration::from_millis(500));
+
+    client
+        .discover_scopes("uuid:some-client-uuid".to_string(),
+                         &addresses,
+                         "some:special:service".to_string())
+        .await.unwrap();
+
+    for service in services {
+        service.shutdown().await.unwrap();
+    }
+
+    let services = client.get_discovered_services();
+    assert_eq!(1, services.len());
+
+    let mut addresses = services[0].addresses.iter().map(|address| address.is_ipv4()).collect::<Vec<_>>();
+    addresses.sort();
+    assert_eq!(vec![false, true], addresses);
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use std::time::Duration;
+use std::time::Instant;
+use simple_ssdp::service::Service;
+use simple_ssdp::Error;
+use common::client;
+use common::private_group;
+use common::some_service;
+use common::start;
+
+#[tokio::test]
+/// The Client returns as soon as the Service answered instead of waiting for the timeout
+async fn test_client_discover_first() {
+    let address = private_group(41903);
+    let service = start(Service::new(some_service()), address).await;
+
+    let mut client = client();
+    client.set_timeout(Duration::from_secs(10));
+
+    let started = Instant::now();
+    let first = client
+        .discover_first("uuid:some-client-uuid".to_string(), address, "some:special:service".to_string())
+        .await.unwrap();
+
+    assert_eq!(some_service(), first);
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    client.set_timeout(Duration::from_millis(500));
+    let missing = client
+        .discover_until("uuid:some-client-uuid".to_string(), address, "ssdp:all".to_string(), |found| {
+            found.iter().any(|service| service.usn_uri == "uuid:missing-service-uuid")
+        })
+        .await;
+
+    service.shutdown().await.unwrap();
+
+    assert!(matches!(missing, Err(Error::Timeout)));
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+use simple_ssdp::client::Client;
+use simple_ssdp::interface::Interface;
+use simple_ssdp::service::Service;
+use common::loopback;
+use common::private_group;
+use common::some_service;
+use common::start;
+
+#[tokio::test]
+/// Client and Service bound to the loopback interface, the Client reports where the Service was found
+async fn test_client_and_service_on_interface() {
+    let address = private_group(41904);
+    let service = start(Service::new(some_service()), address).await;
+
+    let mut client = Client::default();
+    client.set_timeout(Duration::from_millis(500));
+
+    client.set_interfaces(vec![Interface::Name("no-such-interface".to_string())]);
+    assert!(client
+        .discover("uuid:some-client-uuid".to_string(),
+                  address,
+                  "some:special:service".to_string())
+        .await.is_err());
+
+    client.set_interfaces(vec![loopback()]);
+    client
+        .discover("uuid:some-client-uuid".to_string(),
+                  address,
+                  "some:special:service".to_string())
+        .await.unwrap();
+
+    service.shutdown().await.unwrap();
+
+    let services = client.get_discovered_services();
+    assert_eq!(1, services.len());
+    assert_eq!("uuid:some-service-uuid", services[0].description.usn_uri);
+
+    // The interface holding 127.0.0.1, `lo` on Linux
+    let loopback_name = if_addrs::get_if_addrs()
+        .unwrap()
+        .into_iter()
+        .find(|found| found.ip() == IpAddr::V4(Ipv4Addr::LOCALHOST))
+        .unwrap()
+        .name;
+    assert_eq!(Some(loopback_name), services[0].interface);
+}
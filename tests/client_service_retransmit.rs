@@ -0,0 +1,32 @@
+mod common;
+
+use std::time::Duration;
+use simple_ssdp::service::Service;
+use common::client;
+use common::private_group;
+use common::some_service;
+use common::start;
+
+#[tokio::test]
+/// Every M-SEARCH is answered, but the Service is only counted once
+async fn test_client_retransmits_search() {
+    let address = private_group(41905);
+    let service = start(Service::new(some_service()), address).await;
+
+    let mut client = client();
+    client.set_timeout(Duration::from_millis(1500));
+    client.set_search_count(3);
+    let stats = client
+        .discover("uuid:some-client-uuid".to_string(), address, "some:special:service".to_string())
+        .await.unwrap();
+
+    service.shutdown().await.unwrap();
+
+    assert_eq!(3, stats.searches_sent);
+    assert_eq!(1, stats.services_found);
+    assert!(stats.responses >= 3);
+    assert_eq!(3, stats.responses_per_attempt.len());
+    assert!(stats.responses_per_attempt.iter().all(|responses| *responses >= 1));
+    assert_eq!(stats.responses, stats.responses_per_attempt.iter().sum::<usize>());
+    assert_eq!(vec![some_service()], client.get_services());
+}
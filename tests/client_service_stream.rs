@@ -0,0 +1,37 @@
+mod common;
+
+use std::time::Duration;
+use std::time::Instant;
+use simple_ssdp::service::Service;
+use common::client;
+use common::private_group;
+use common::some_service;
+use common::start;
+
+#[tokio::test]
+/// Each Service is streamed once as soon as it answers, the stream ends with the timeout
+async fn test_client_discover_stream() {
+    let address = private_group(41906);
+    let service = start(Service::new(some_service()), address).await;
+
+    let mut client = client();
+    client.set_timeout(Duration::from_secs(3));
+
+    let started = Instant::now();
+    let mut responses = client
+        .discover_stream("uuid:some-client-uuid".to_string(), address, "some:special:service".to_string())
+        .await.unwrap();
+
+    let first = responses.recv().await.unwrap();
+    assert_eq!(some_service(), first.description);
+    assert!(first.source.ip().is_loopback());
+    assert!(first.received_at >= started);
+    assert!(first.received_at.duration_since(started) < Duration::from_secs(2));
+
+    assert!(responses.recv().await.is_none());
+    assert!(started.elapsed() >= Duration::from_secs(3));
+
+    service.shutdown().await.unwrap();
+
+    assert_eq!(vec![some_service()], client.get_services());
+}
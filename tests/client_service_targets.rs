@@ -0,0 +1,36 @@
+mod common;
+
+use std::time::Duration;
+use simple_ssdp::service::Service;
+use common::client;
+use common::desc;
+use common::private_group;
+use common::start;
+
+#[tokio::test]
+/// One search finds the Services of several types, each attributed to every target it answered
+async fn test_client_discover_targets() {
+    let address = private_group(41907);
+    let service = Service::new(desc("first"));
+    service.add_service(desc("second")).await.unwrap();
+    service.add_service(desc("third")).await.unwrap();
+    let service = start(service, address).await;
+
+    let mut client = client();
+    client.set_timeout(Duration::from_millis(1500));
+    let stats = client
+        .discover_targets("uuid:some-client-uuid".to_string(),
+                          address,
+                          vec!["some:first:service".to_string(), "some:second:service".to_string(), "ssdp:all".to_string()])
+        .await.unwrap();
+
+    service.shutdown().await.unwrap();
+
+    let mut usns: Vec<String> = client.get_services().into_iter().map(|service| service.usn_uri).collect();
+    usns.sort();
+    assert_eq!(vec!["uuid:first-uuid", "uuid:second-uuid", "uuid:third-uuid"], usns);
+    assert_eq!(3, stats.services_found);
+    assert_eq!(Some(&1), stats.services_per_target.get("some:first:service"));
+    assert_eq!(Some(&1), stats.services_per_target.get("some:second:service"));
+    assert_eq!(Some(&3), stats.services_per_target.get("ssdp:all"));
+}
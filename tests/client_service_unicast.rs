@@ -0,0 +1,35 @@
+mod common;
+
+use std::time::Duration;
+use std::time::Instant;
+use simple_ssdp::client::Client;
+use simple_ssdp::service::Service;
+use simple_ssdp::Error;
+use common::private_group;
+use common::some_service;
+use common::start;
+
+#[tokio::test]
+/// A Service answers a unicast M-SEARCH right away, as it carries no MX, and the search ends with its answer
+async fn test_client_search_unicast() {
+    let service = start(Service::new(some_service()), private_group(41908)).await;
+
+    let mut client = Client::default();
+
+    let started = Instant::now();
+    let answered = client
+        .search_unicast("uuid:some-client-uuid".to_string(), "127.0.0.1:41908".parse().unwrap(), "some:special:service".to_string())
+        .await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(vec![some_service()], answered);
+
+    client.set_timeout(Duration::from_millis(300));
+    let missing = client
+        .search_unicast("uuid:some-client-uuid".to_string(), "127.0.0.1:41908".parse().unwrap(), "some:other:service".to_string())
+        .await;
+
+    service.shutdown().await.unwrap();
+
+    assert!(matches!(missing, Err(Error::Timeout)));
+    assert_eq!(vec![some_service()], client.get_services());
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use std::time::Duration;
+use simple_ssdp::service::Service;
+use simple_ssdp::service::ServiceDescription;
+use simple_ssdp::Protocol;
+use common::client;
+use common::private_group;
+use common::start;
+
+#[tokio::test]
+/// Spinning up a client and a service speaking UPnP on localhost
+async fn test_client_and_service_communication_upnp() {
+    let desc = ServiceDescription {
+        usn_uri: "uuid:some-service-uuid::urn:schemas-upnp-org:device:Basic:1".to_string(),
+        service_type_uri: "urn:schemas-upnp-org:device:Basic:1".to_string(),
+        expiration: 1800,
+        location: "http://127.0.0.1:8080/description.xml".to_string(),
+    };
+    let address = private_group(41909);
+
+    let mut service = Service::new(desc.clone());
+    service.set_protocol(Protocol::Upnp);
+    service.set_server("Linux/6.1 UPnP/2.0 test/1.0".to_string());
+    service.set_boot_id(42);
+    let service = start(service, address).await;
+
+    let mut client = client();
+    client.set_timeout(Duration::from_millis(500));
+    client.set_protocol(Protocol::Upnp);
+
+    client
+        .discover("uuid:some-client-uuid".to_string(),
+                  address,
+                  "urn:schemas-upnp-org:device:Basic:1".to_string())
+        .await.unwrap();
+
+    service.shutdown().await.unwrap();
+
+    let discovered = client.get_discovered_services();
+
+    assert_eq!(1, discovered.len());
+    assert_eq!(desc, discovered[0].description);
+    assert_eq!(Some("Linux/6.1 UPnP/2.0 test/1.0".to_string()), discovered[0].server);
+    assert_eq!(Some(42), discovered[0].boot_id);
+    assert_eq!(Some(1), discovered[0].config_id);
+}
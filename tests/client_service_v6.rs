@@ -0,0 +1,39 @@
+mod common;
+
+use std::time::Duration;
+use simple_ssdp::client::Client;
+use simple_ssdp::service::Service;
+use simple_ssdp::service::ServiceDescription;
+use common::private_v6;
+use common::some_service;
+use common::start;
+
+#[tokio::test]
+/// Spinning up a client and a service on the IPv6 localhost `::1`
+/// 
+/// This takes a while to reach the client's timeout.
+async fn test_client_and_service_communication_v6() {
+    let address = private_v6(41910);
+    let service = start(Service::new(some_service()), address).await;
+
+    let mut client = Client::default();
+    client
+        .set_timeout(Duration::from_millis(500));
+
+    client
+        .discover("uuid:some-client-uuid".to_string(),
+                  address,
+                  "some:special:service".to_string())
+        .await.unwrap();
+
+    service.shutdown().await.unwrap();
+    
+    let expected: Vec<ServiceDescription> = vec![ServiceDescription{
+        usn_uri: "uuid:some-service-uuid".to_string(),
+        service_type_uri: "some:special:service".to_string(),
+        expiration: 100,
+        location: "https://foo/bar".to_string(),
+    }];
+    
+    assert_eq!(expected, client.get_services());
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use simple_ssdp::client::ClientEvent;
+use simple_ssdp::service::ServiceDescription;
+use tokio::net::UdpSocket;
+use common::client;
+use common::next_event;
+use common::private_group;
+
+#[tokio::test]
+/// Notifications sent to the port watched show up as events
+async fn test_client_watch() {
+    let client = client();
+    let mut events = client.watch(private_group(41911)).await.unwrap();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let notify = |nts: &str, location: &str, max_age: u32| {
+        format!(
+            "NOTIFY * HTTP/1.1\r\nHost: 239.255.77.77:41911\r\nNT: some:special:service\r\nNTS: {}\r\nUSN: uuid:some-service-uuid\r\nAL: <uuid:some-service-uuid><{}>\r\nCache-Control: max-age = {}\r\n\r\n",
+            nts, location, max_age
+        )
+    };
+
+    let mut expected = ServiceDescription {
+        usn_uri: "uuid:some-service-uuid".to_string(),
+        service_type_uri: "some:special:service".to_string(),
+        expiration: 100,
+        location: "https://foo/bar".to_string(),
+    };
+
+    socket
+        .send_to(notify("ssdp:alive", &expected.location, 100).as_bytes(), "127.0.0.1:41911")
+        .await
+        .unwrap();
+    assert_eq!(ClientEvent::ServiceAppeared(expected.clone()), next_event(&mut events).await);
+    assert_eq!(vec![expected.clone()], client.get_services());
+
+    expected.location = "https://foo/baz".to_string();
+    socket
+        .send_to(notify("ssdp:alive", &expected.location, 100).as_bytes(), "127.0.0.1:41911")
+        .await
+        .unwrap();
+    assert_eq!(ClientEvent::ServiceUpdated(expected.clone()), next_event(&mut events).await);
+
+    socket
+        .send_to(notify("ssdp:byebye", &expected.location, 100).as_bytes(), "127.0.0.1:41911")
+        .await
+        .unwrap();
+    assert_eq!(ClientEvent::ServiceByebye(expected.clone()), next_event(&mut events).await);
+    assert!(client.get_services().is_empty());
+
+    expected.expiration = 1;
+    socket
+        .send_to(notify("ssdp:alive", &expected.location, 1).as_bytes(), "127.0.0.1:41911")
+        .await
+        .unwrap();
+    assert_eq!(ClientEvent::ServiceAppeared(expected.clone()), next_event(&mut events).await);
+    assert_eq!(ClientEvent::ServiceExpired(expected), next_event(&mut events).await);
+    assert!(client.get_services().is_empty());
+    assert!(client.get_discovered_services().is_empty());
+}
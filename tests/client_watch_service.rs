@@ -0,0 +1,25 @@
+mod common;
+
+use simple_ssdp::client::ClientEvent;
+use simple_ssdp::service::Service;
+use common::client;
+use common::next_event;
+use common::private_group;
+use common::some_service;
+use common::start;
+
+#[tokio::test]
+/// A Service's multicasted NOTIFY reach a Client watching on the same host
+async fn test_client_watch_service() {
+    let address = private_group(41912);
+    let client = client();
+    let mut events = client.watch(address).await.unwrap();
+
+    let service = start(Service::new(some_service()), address).await;
+
+    assert_eq!(ClientEvent::ServiceAppeared(some_service()), next_event(&mut events).await);
+
+    service.shutdown().await.unwrap();
+
+    assert_eq!(ClientEvent::ServiceByebye(some_service()), next_event(&mut events).await);
+}
@@ -0,0 +1,79 @@
+//! Helpers shared by the integration tests
+#![allow(dead_code)]
+
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::time::Duration;
+use simple_ssdp::client::Client;
+use simple_ssdp::client::ClientEvent;
+use simple_ssdp::interface::Interface;
+use simple_ssdp::service::Service;
+use simple_ssdp::service::ServiceDescription;
+use simple_ssdp::service::ServiceHandle;
+use simple_ssdp::MulticastAddr;
+use tokio::sync::mpsc::Receiver;
+
+/// A private multicast group on `port`, to be joined on the [loopback] interface
+///
+/// Every test file uses a port of its own, so tests running at once don't hear each other
+/// and none of them gets in the way of an SSDP daemon on port 1900.
+pub fn private_group(port: u16) -> MulticastAddr {
+    MulticastAddr::Custom(SocketAddr::from(([239, 255, 77, 77], port)))
+}
+
+/// The IPv6 localhost on `port`, sent to directly instead of joining a group
+pub fn private_v6(port: u16) -> MulticastAddr {
+    MulticastAddr::Custom(SocketAddr::from((Ipv6Addr::LOCALHOST, port)))
+}
+
+/// The loopback interface, so multicasts to a [private_group] never leave this host
+pub fn loopback() -> Interface {
+    Interface::Addr(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
+
+/// The Service most tests search for
+pub fn some_service() -> ServiceDescription {
+    ServiceDescription {
+        usn_uri: "uuid:some-service-uuid".to_string(),
+        service_type_uri: "some:special:service".to_string(),
+        expiration: 100,
+        location: "https://foo/bar".to_string(),
+    }
+}
+
+/// A Service of its own type, for tests with several of them: `uuid:{name}-uuid` of type `some:{name}:service`
+pub fn desc(name: &str) -> ServiceDescription {
+    ServiceDescription {
+        usn_uri: format!("uuid:{}-uuid", name),
+        service_type_uri: format!("some:{}:service", name),
+        expiration: 100,
+        location: format!("https://foo/{}", name),
+    }
+}
+
+/// Starts `service` at `address` on the [loopback] interface and waits until it listens
+pub async fn start(mut service: Service, address: MulticastAddr) -> ServiceHandle {
+    service.set_interfaces(vec![loopback()]);
+    let handle = service.start(address);
+    assert!(handle.ready().await, "service did not start");
+
+    handle
+}
+
+/// A Client searching on the [loopback] interface
+pub fn client() -> Client {
+    let mut client = Client::default();
+    client.set_interfaces(vec![loopback()]);
+
+    client
+}
+
+/// The next event of [Client::watch], failing the test if none arrives within 5 seconds
+pub async fn next_event(events: &mut Receiver<ClientEvent>) -> ClientEvent {
+    tokio::time::timeout(Duration::from_secs(5), events.recv())
+        .await
+        .expect("no event received")
+        .expect("watcher stopped")
+}
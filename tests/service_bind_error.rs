@@ -0,0 +1,30 @@
+use std::net::UdpSocket;
+use simple_ssdp::service::Service;
+use simple_ssdp::service::ServiceDescription;
+use simple_ssdp::Error;
+use simple_ssdp::MulticastAddr;
+
+#[tokio::test]
+/// A Service fails with a bind error instead of panicking when another program owns its port
+async fn test_service_bind_error() {
+    // Bound without SO_REUSEADDR, so nobody can share the port
+    let owner = UdpSocket::bind("0.0.0.0:0").unwrap();
+    let port = owner.local_addr().unwrap().port();
+
+    let service = Service::new(ServiceDescription {
+        usn_uri: "uuid:some-service-uuid".to_string(),
+        service_type_uri: "some:special:service".to_string(),
+        expiration: 100,
+        location: "https://foo/bar".to_string(),
+    });
+
+    let address = MulticastAddr::Custom(format!("239.255.77.77:{}", port).parse().unwrap());
+    let result = service.listen(address).await;
+
+    assert!(matches!(result, Err(Error::Bind(_))), "unexpected result {:?}", result);
+
+    // Started in the background, the handle never gets ready and returns the error
+    let handle = service.start(address);
+    assert!(!handle.ready().await);
+    assert!(matches!(handle.shutdown().await, Err(Error::Bind(_))));
+}
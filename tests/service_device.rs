@@ -0,0 +1,52 @@
+mod common;
+
+use std::time::Duration;
+use simple_ssdp::device::Device;
+use simple_ssdp::device::EmbeddedDevice;
+use simple_ssdp::service::Service;
+use common::client;
+use common::private_group;
+use common::start;
+
+#[tokio::test]
+/// A device tree answers `ssdp:all` with the root, uuid, device type and service type of every device
+async fn test_service_device_announces_tree() {
+    let device = Device {
+        udn: "uuid:root".to_string(),
+        device_type: "urn:schemas-upnp-org:device:MediaServer:1".to_string(),
+        expiration: 1800,
+        location: "http://127.0.0.1:8200/rootDesc.xml".to_string(),
+        service_types: vec!["urn:schemas-upnp-org:service:ContentDirectory:1".to_string()],
+        embedded_devices: vec![EmbeddedDevice {
+            udn: "uuid:embedded".to_string(),
+            device_type: "urn:schemas-upnp-org:device:Printer:1".to_string(),
+            service_types: vec!["urn:schemas-upnp-org:service:ConnectionManager:1".to_string()],
+            embedded_devices: vec![],
+        }],
+    };
+    let address = private_group(41913);
+    let service = start(Service::from_device(&device), address).await;
+
+    let mut client = client();
+    client.set_timeout(Duration::from_millis(1500));
+    client
+        .discover("uuid:some-client-uuid".to_string(), address, "ssdp:all".to_string())
+        .await.unwrap();
+
+    service.shutdown().await.unwrap();
+
+    let mut usns: Vec<String> = client.get_services().into_iter().map(|service| service.usn_uri).collect();
+    usns.sort();
+    assert_eq!(
+        vec![
+            "uuid:embedded",
+            "uuid:embedded::urn:schemas-upnp-org:device:Printer:1",
+            "uuid:embedded::urn:schemas-upnp-org:service:ConnectionManager:1",
+            "uuid:root",
+            "uuid:root::upnp:rootdevice",
+            "uuid:root::urn:schemas-upnp-org:device:MediaServer:1",
+            "uuid:root::urn:schemas-upnp-org:service:ContentDirectory:1",
+        ],
+        usns
+    );
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use std::time::Duration;
+use simple_ssdp::service::Service;
+use tokio::net::UdpSocket;
+use common::private_group;
+use common::some_service;
+use common::start;
+
+#[tokio::test]
+/// Shutting down a started Service releases its port, even with answers still waiting for their delay
+async fn test_service_handle_shutdown() {
+    let port = std::net::UdpSocket::bind("0.0.0.0:0").unwrap().local_addr().unwrap().port();
+    let handle = start(Service::new(some_service()), private_group(port)).await;
+    assert_eq!(1, handle.service().get_services().len());
+
+    // Bound with SO_REUSEADDR by the Service, so a socket without it can't share the port
+    assert!(std::net::UdpSocket::bind(("0.0.0.0", port)).is_err());
+
+    // Answered after up to 5 seconds
+    let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    client
+        .send_to(
+            format!("M-SEARCH * HTTP/1.1\r\nS: uuid:some-client-uuid\r\nHost: 127.0.0.1:{}\r\nMAN: \"ssdp:discover\"\r\nST: ssdp:all\r\nMX: 5\r\n\r\n", port).as_bytes(),
+            ("127.0.0.1", port),
+        )
+        .await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let result = tokio::time::timeout(Duration::from_secs(1), handle.shutdown()).await;
+    assert!(result.is_ok(), "service did not stop");
+    assert!(result.unwrap().is_ok());
+
+    // The port is free again
+    assert!(std::net::UdpSocket::bind(("0.0.0.0", port)).is_ok());
+}
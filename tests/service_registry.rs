@@ -0,0 +1,48 @@
+mod common;
+
+use std::time::Duration;
+use simple_ssdp::service::Service;
+use common::client;
+use common::desc;
+use common::private_group;
+use common::start;
+
+async fn discover_all() -> Vec<String> {
+    let mut client = client();
+    client.set_mx(0);
+    client.set_timeout(Duration::from_millis(500));
+
+    client
+        .discover("uuid:some-client-uuid".to_string(),
+                  private_group(41914),
+                  "ssdp:all".to_string())
+        .await.unwrap();
+
+    let mut usns: Vec<String> = client.get_services().into_iter().map(|service| service.usn_uri).collect();
+    usns.sort();
+    usns
+}
+
+#[tokio::test]
+/// One listener answers `ssdp:all` once per registration, also for those added and removed while listening
+async fn test_service_registry() {
+    let service = Service::new(desc("first"));
+    service.add_service(desc("second")).await.unwrap();
+
+    let handle = start(service, private_group(41914)).await;
+    let service = handle.service();
+
+    service.add_service(desc("third")).await.unwrap();
+    assert_eq!(vec!["uuid:first-uuid", "uuid:second-uuid", "uuid:third-uuid"], discover_all().await);
+
+    assert_eq!(Some(desc("second")), service.remove_service("uuid:second-uuid").await.unwrap());
+    assert_eq!(None, service.remove_service("uuid:second-uuid").await.unwrap());
+    assert_eq!(vec!["uuid:first-uuid", "uuid:third-uuid"], discover_all().await);
+
+    let mut moved = desc("third");
+    moved.location = "https://foo/elsewhere".to_string();
+    assert_eq!(Some(desc("third")), service.update_service(moved.clone()).await.unwrap());
+    assert_eq!(vec![desc("first"), moved], service.get_services());
+
+    handle.shutdown().await.unwrap();
+}
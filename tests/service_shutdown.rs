@@ -0,0 +1,56 @@
+mod common;
+
+use std::collections::HashSet;
+use std::net::Ipv4Addr;
+use std::net::SocketAddr;
+use std::time::Duration;
+use simple_ssdp::message::SsdpMessage;
+use simple_ssdp::service::Service;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+use common::desc;
+use common::loopback;
+use common::private_group;
+
+/// Joins the group of the Service on the loopback interface, next to it
+fn group_receiver() -> UdpSocket {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).unwrap();
+    socket.set_reuse_address(true).unwrap();
+    #[cfg(unix)]
+    socket.set_reuse_port(true).unwrap();
+    socket.set_nonblocking(true).unwrap();
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, 41915)).into()).unwrap();
+    socket.join_multicast_v4(&Ipv4Addr::new(239, 255, 77, 77), &Ipv4Addr::LOCALHOST).unwrap();
+
+    UdpSocket::from_std(socket.into()).unwrap()
+}
+
+#[tokio::test]
+/// The listener returns once the shutdown future completes, saying `ssdp:byebye` for every registration
+async fn test_service_listen_until_shutdown() {
+    let receiver = group_receiver();
+
+    let mut service = Service::new(desc("first"));
+    service.set_interfaces(vec![loopback()]);
+    service.add_service(desc("second")).await.unwrap();
+    let shutdown = tokio::time::sleep(Duration::from_millis(100));
+
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        service.listen_until(private_group(41915), shutdown),
+    )
+    .await;
+
+    assert!(result.is_ok(), "listener did not stop");
+    assert!(result.unwrap().is_ok());
+
+    let mut byebye = HashSet::new();
+    let mut buf = vec![0; 1024];
+    while let Ok(Ok(len)) = tokio::time::timeout(Duration::from_millis(500), receiver.recv(&mut buf)).await {
+        if let Ok(SsdpMessage::NotifyByebye { usn, .. }) = SsdpMessage::parse(&buf[..len]) {
+            byebye.insert(usn);
+        }
+    }
+
+    assert_eq!(HashSet::from(["uuid:first-uuid".to_string(), "uuid:second-uuid".to_string()]), byebye);
+}